@@ -11,7 +11,7 @@ const addAdmin = async (
   program: Program,
 ) => {
   const ix = await program.methods
-    .addAdmin(admin)
+    .addAdmin(admin, null, true, true)
     .accounts({
       payer: payer.publicKey,
     })
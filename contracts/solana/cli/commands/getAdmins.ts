@@ -16,7 +16,9 @@ const getAdmins = async (
 
   console.log(programState.admins.length > 0 ? "Admins:" : "There are no admins except the owner");
   programState.admins.forEach((admin, index) => {
-    console.log(`${index + 1}: ${admin.toString()}`);
+    const expiry = admin.expiresTs ? ` (expires ${new Date(admin.expiresTs.toNumber() * 1000).toISOString()})` : "";
+    const permissions = [admin.canWithdraw && "withdraw", admin.canManage && "manage"].filter(Boolean).join(", ") || "none";
+    console.log(`${index + 1}: ${admin.key.toString()} [${permissions}]${expiry}`);
   });
 };
 
@@ -0,0 +1,35 @@
+import { Program } from "@coral-xyz/anchor";
+import { Connection, PublicKey } from "@solana/web3.js";
+import { program } from "..";
+import idl from "../../target/idl/libertai_payment_processor.json";
+import { LibertaiPaymentProcessor } from "../../target/types/libertai_payment_processor";
+import { getPublicStatsAccount } from "../utils";
+
+const getPublicStats = async (
+  anchorProgram: Program<LibertaiPaymentProcessor>,
+  tokenMint: PublicKey
+) => {
+  const publicStatsPDA = getPublicStatsAccount(anchorProgram.programId, tokenMint);
+
+  const publicStats = await anchorProgram.account.publicStats.fetchNullable(publicStatsPDA);
+  if (!publicStats) {
+    console.log(`No payments recorded yet for ${tokenMint.toString()}`);
+    return;
+  }
+
+  console.log(`Public stats of ${tokenMint.toString()} (${publicStatsPDA.toString()}):`);
+  console.log(`Total processed: ${publicStats.totalProcessed.toString()}`);
+  console.log(`Payment count: ${publicStats.paymentCount.toString()}`);
+  console.log(`Last update slot: ${publicStats.lastUpdateSlot.toString()}`);
+};
+
+export const GetPublicStatsCommand = async () => {
+  const opts = program.opts();
+
+  const connection = new Connection(opts.jsonRpcEndpoint, "confirmed");
+  const anchorProgram = new Program<LibertaiPaymentProcessor>(idl, {
+    connection,
+  });
+
+  await getPublicStats(anchorProgram, new PublicKey(opts.tokenMint));
+};
@@ -1,15 +1,30 @@
 import { Program } from "@coral-xyz/anchor";
-import { Connection, Keypair, sendAndConfirmTransaction, Transaction } from "@solana/web3.js";
+import {
+  Connection,
+  Keypair,
+  PublicKey,
+  sendAndConfirmTransaction,
+  Transaction,
+} from "@solana/web3.js";
 import { program } from "..";
 import idl from "../../target/idl/libertai_payment_processor.json";
 import { LibertaiPaymentProcessor } from "../../target/types/libertai_payment_processor";
 import { getKeypair } from "../utils";
 
+const BPF_LOADER_UPGRADEABLE_PROGRAM_ID = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+
 const initialize = async (payer: Keypair, program: Program) => {
+  // Initialization must be signed by the program's upgrade authority
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+  );
+
   const initializeIx = await program.methods
     .initialize(payer.publicKey)
     .accounts({
       payer: payer.publicKey,
+      programData,
     })
     .instruction();
 
@@ -0,0 +1,36 @@
+import { Program } from "@coral-xyz/anchor";
+import { Connection, Keypair, sendAndConfirmTransaction, Transaction } from "@solana/web3.js";
+import { program } from "..";
+import idl from "../../target/idl/libertai_payment_processor.json";
+import { LibertaiPaymentProcessor } from "../../target/types/libertai_payment_processor";
+import { getKeypair } from "../utils";
+
+const migrateProgramState = async (payer: Keypair, program: Program) => {
+  // Must be signed by the owner stored in the old program_state, who also pays the extra rent
+  const ix = await program.methods
+    .migrateProgramState()
+    .accounts({
+      authority: payer.publicKey,
+    })
+    .instruction();
+
+  const tx = new Transaction().add(ix);
+  const sig = await sendAndConfirmTransaction(program.provider.connection, tx, [payer]);
+  console.log(`✅ Migrated program state. Tx Signature: ${sig}`);
+};
+
+export const MigrateProgramStateCommand = async () => {
+  const opts = program.opts();
+
+  const payer = getKeypair({
+    filepath: opts.payerKeyFilepath,
+    key: opts.payerPrivateKey,
+  });
+
+  const connection = new Connection(opts.jsonRpcEndpoint, "confirmed");
+  const anchorProgram = new Program(idl as LibertaiPaymentProcessor, {
+    connection,
+  });
+
+  await migrateProgramState(payer, anchorProgram);
+};
@@ -10,7 +10,7 @@ import {
 import { program } from "..";
 import idl from "../../target/idl/libertai_payment_processor.json";
 import { LibertaiPaymentProcessor } from "../../target/types/libertai_payment_processor";
-import { getKeypair, getTokenProgramId } from "../utils";
+import { getKeypair, getProgramTokenAccount, getTokenProgramId } from "../utils";
 
 const processPayment = async (
   payer: Keypair,
@@ -26,23 +26,28 @@ const processPayment = async (
     false,
     tokenProgramId
   );
-  const [programTokenAccountPDA] = PublicKey.findProgramAddressSync(
-    [
-      Buffer.from("program_token_account"),
-      tokenMint.toBuffer()
-    ],
+  const [mintStatePDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("mint_state"), tokenMint.toBuffer()],
     program.programId
   );
+  const mintState = await program.account.mintState.fetch(mintStatePDA);
+  const programTokenAccountPDA = getProgramTokenAccount(
+    program.programId,
+    tokenMint,
+    mintState.programTokenSeedVersion
+  );
 
 
   const ix = await program.methods
-    .processPayment(amount)
+    .processPayment(amount, null, "")
     .accounts({
       user: payer.publicKey,
       userTokenAccount,
       programTokenAccount: programTokenAccountPDA,
       tokenMint,
       tokenProgram: tokenProgramId,
+      coldTreasury: mintState.coldTreasury,
+      paymentReceipt: null,
     })
     .instruction();
 
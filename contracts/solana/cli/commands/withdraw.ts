@@ -4,7 +4,7 @@ import { Connection, Keypair, PublicKey, sendAndConfirmTransaction, Transaction
 import { program } from "..";
 import idl from "../../target/idl/libertai_payment_processor.json";
 import { LibertaiPaymentProcessor } from "../../target/types/libertai_payment_processor";
-import { getKeypair, getTokenProgramId } from "../utils";
+import { getKeypair, getProgramTokenAccount, getTokenProgramId } from "../utils";
 
 interface SolanaRpcResponse {
   jsonrpc: string;
@@ -25,13 +25,7 @@ interface SolanaRpcResponse {
   error?: any;
 }
 
-const getBalance = async (tokenMint: PublicKey, programId: PublicKey, networkURL: string): Promise<number> => {
-  // Derive the program token account PDA (same as in withdraw function)
-  const [programTokenAccount] = PublicKey.findProgramAddressSync(
-    [Buffer.from("program_token_account"), tokenMint.toBuffer()],
-    programId
-  );
-
+const getBalance = async (programTokenAccount: PublicKey, networkURL: string): Promise<number> => {
   const body = {
     "jsonrpc": "2.0",
     "id": 1,
@@ -66,15 +60,14 @@ const getBalance = async (tokenMint: PublicKey, programId: PublicKey, networkURL
 
 
 const waitForBalanceChange = async (
-  tokenMint: PublicKey,
-  programId: PublicKey,
+  programTokenAccount: PublicKey,
   networkURL: string,
   expectedBalance: number,
   maxRetries: number = 10,
   retryDelayMs: number = 2000
 ): Promise<number> => {
   for (let i = 0; i < maxRetries; i++) {
-    const currentBalance = await getBalance(tokenMint, programId, networkURL);
+    const currentBalance = await getBalance(programTokenAccount, networkURL);
     if (currentBalance !== expectedBalance) {
       return currentBalance;
     }
@@ -82,7 +75,7 @@ const waitForBalanceChange = async (
       await new Promise(resolve => setTimeout(resolve, retryDelayMs));
     }
   }
-  return await getBalance(tokenMint, programId, networkURL);
+  return await getBalance(programTokenAccount, networkURL);
 };
 
 const withdraw = async (
@@ -107,8 +100,15 @@ const withdraw = async (
     program.programId
   );
 
-  const [programTokenAccount] = PublicKey.findProgramAddressSync(
-    [Buffer.from("program_token_account"), tokenMint.toBuffer()],
+  const [mintState] = PublicKey.findProgramAddressSync(
+    [Buffer.from("mint_state"), tokenMint.toBuffer()],
+    program.programId
+  );
+  const { programTokenSeedVersion } = await program.account.mintState.fetch(mintState);
+  const programTokenAccount = getProgramTokenAccount(program.programId, tokenMint, programTokenSeedVersion);
+
+  const [kycRegistry] = PublicKey.findProgramAddressSync(
+    [Buffer.from("kyc"), destinationWallet.toBuffer()],
     program.programId
   );
 
@@ -121,19 +121,20 @@ const withdraw = async (
       authority: payer.publicKey,
       programTokenAccount: programTokenAccount,
       destinationTokenAccount: destinationTokenAccount,
+      kycRegistry: kycRegistry,
       tokenMint: tokenMint,
       tokenProgram: tokenProgramId,
     })
     .instruction();
 
-  const balanceBefore = await getBalance(tokenMint, program.programId, networkURL)
+  const balanceBefore = await getBalance(programTokenAccount, networkURL)
   console.log(`Program balance before withdraw is ${balanceBefore}`)
 
   const tx = new Transaction().add(ix);
   const sig = await sendAndConfirmTransaction(program.provider.connection, tx, [payer]);
 
   console.log("Waiting for balance update...");
-  const balanceAfter = await waitForBalanceChange(tokenMint, program.programId, networkURL, balanceBefore);
+  const balanceAfter = await waitForBalanceChange(programTokenAccount, networkURL, balanceBefore);
   console.log(`Program balance after withdraw is ${balanceAfter}`)
   console.log(`✅ Withdrew ${amount.toString()} tokens to ${destinationWallet.toString()}. Tx Signature: ${sig}`);
 }
@@ -1,10 +1,12 @@
 import { Command } from "commander";
 import { InitializeCommand } from "./commands/initialize";
+import { MigrateProgramStateCommand } from "./commands/migrateProgramState";
 import { AddAdminCommand } from "./commands/addAdmin";
 import { ProcessPaymentCommand } from "./commands/processPayment";
 import { RemoveAdminCommand } from "./commands/removeAdmin";
 import { ChangeOwnerCommand } from "./commands/changeOwner";
 import { GetAdminsCommand } from "./commands/getAdmins";
+import { GetPublicStatsCommand } from "./commands/getPublicStats";
 import { WithdrawCommand } from "./commands/withdraw";
 import { WithdrawSolCommand } from "./commands/withdraw_sol";
 import * as os from "os";
@@ -30,6 +32,11 @@ program
   .description("Initialize the program")
   .action(InitializeCommand);
 
+program
+  .command("migrate-program-state")
+  .description("Migrate the program state from the layout first deployed (owner only)")
+  .action(MigrateProgramStateCommand);
+
 program
   .command("process-payment")
   .description("Process a payment and emit an event")
@@ -60,6 +67,11 @@ program
   .description("Get the admins of the program")
   .action(GetAdminsCommand)
 
+program
+  .command("get-public-stats")
+  .description("Get the payment totals of a token mint")
+  .action(GetPublicStatsCommand)
+
 program
   .command("withdraw")
   .description("Withdraw LTAI tokens from program (admin/owner only)")
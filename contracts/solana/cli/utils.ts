@@ -26,3 +26,20 @@ export const getTokenProgramId = async (
     ? TOKEN_2022_PROGRAM_ID 
     : TOKEN_PROGRAM_ID;
 };
+
+// Program token account of a mint for its current seed version, version 0 keeps the original address
+export const getProgramTokenAccount = (
+  programId: PublicKey,
+  tokenMint: PublicKey,
+  seedVersion: number
+): PublicKey => {
+  const seeds = [Buffer.from("program_token_account"), tokenMint.toBuffer()];
+  if (seedVersion !== 0) {
+    seeds.push(Buffer.from([seedVersion]));
+  }
+  return PublicKey.findProgramAddressSync(seeds, programId)[0];
+};
+
+// PublicStats is kept per mint, at ["public_stats", mint]
+export const getPublicStatsAccount = (programId: PublicKey, tokenMint: PublicKey): PublicKey =>
+  PublicKey.findProgramAddressSync([Buffer.from("public_stats"), tokenMint.toBuffer()], programId)[0];
@@ -1,4 +1,6 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
 
 declare_id!("21tHgbgC8Q6hoXyHx5pp3Gtp97B3HxrS2yH832ryeNSw");
 
@@ -6,440 +8,4193 @@ pub const ACCEPTED_MINT: Pubkey = pubkey!("mntpN8z1d29f3MWhMD7VqZFpeYmbD88MgwS3B
 
 pub const SPL_TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
 pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub const ACCEPTED_TOKEN_PROGRAMS: [Pubkey; 2] = [SPL_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID];
+
+pub const MAX_BPS: u16 = 10_000;
+pub const ROUNDING_FLOOR: u8 = 0;
+pub const ROUNDING_HALF_UP: u8 = 1;
+pub const ROUNDING_CEIL: u8 = 2;
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+pub const MAX_REFUND_BATCH: usize = 10;
+pub const MAX_ACCEPTED_MINTS: usize = 16;
+pub const MAX_ADMINS: usize = 23;
+pub const MAX_MEMO_LEN: usize = 64;
+
+// Instruction categories of `ProgramState::pause_mask`
+pub const PAUSE_PAYMENTS: u32 = 1 << 0;
+pub const PAUSE_WITHDRAWALS: u32 = 1 << 1;
+pub const PAUSE_REFUNDS: u32 = 1 << 2;
+pub const PAUSE_ADMIN_CHANGES: u32 = 1 << 3;
+// Upper bound for the program state account, checked wherever it is created or grown
+pub const MAX_PROGRAM_STATE_SIZE: usize = 10 * 1024;
+// A full admin list and mint list must still fit under the cap
+const _: () = assert!(ProgramState::space(MAX_ADMINS, MAX_ACCEPTED_MINTS) <= MAX_PROGRAM_STATE_SIZE);
+
+// program id (32) + user (32) + token mint (32) + amount (8) + discount_bps (2) + nonce (8)
+const DISCOUNT_MESSAGE_LEN: usize = 32 + 32 + 32 + 8 + 2 + 8;
+
+// SPL Token accounts are exactly 165 bytes. Token-2022 keeps the same base layout and, when
+// extensions are present, appends an account type byte (2 = Account) followed by the extension TLVs.
+// Mints with extensions are padded to the same offset and use account type 1.
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+const MINT_LEN: usize = 82;
+const MINT_DECIMALS_OFFSET: usize = 44;
+const TOKEN_2022_ACCOUNT_TYPE_OFFSET: usize = TOKEN_ACCOUNT_LEN;
+const TOKEN_2022_ACCOUNT_TYPE_MINT: u8 = 1;
+const TOKEN_2022_ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+const TOKEN_2022_TLV_OFFSET: usize = TOKEN_2022_ACCOUNT_TYPE_OFFSET + 1;
+// TransferFeeConfig: two authorities + withheld amount, then the older and newer
+// TransferFee { epoch: u64, maximum_fee: u64, transfer_fee_basis_points: u16 }
+const TOKEN_2022_EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+const TRANSFER_FEE_CONFIG_LEN: usize = 32 + 32 + 8 + 18 + 18;
+const OLDER_TRANSFER_FEE_OFFSET: usize = 32 + 32 + 8;
+const NEWER_TRANSFER_FEE_OFFSET: usize = OLDER_TRANSFER_FEE_OFFSET + 18;
 
 fn is_valid_token_program(program_id: &Pubkey) -> bool {
-    *program_id == SPL_TOKEN_PROGRAM_ID || *program_id == TOKEN_2022_PROGRAM_ID
+    ACCEPTED_TOKEN_PROGRAMS.contains(program_id)
 }
 
-#[program]
-pub mod libertai_payment_processor {
-    use super::*;
+// `amount * bps / MAX_BPS`, rounded according to one of the ROUNDING_* modes
+pub fn apply_bps(amount: u64, bps: u16, rounding_mode: u8) -> u64 {
+    let product = amount as u128 * bps as u128;
+    let denominator = MAX_BPS as u128;
+    let result = match rounding_mode {
+        ROUNDING_HALF_UP => (product + denominator / 2) / denominator,
+        ROUNDING_CEIL => product.div_ceil(denominator),
+        _ => product / denominator,
+    };
+    result as u64
+}
 
-    pub fn initialize(ctx: Context<Initialize>, owner: Pubkey) -> Result<()> {
-        let program_state = &mut ctx.accounts.program_state;
+// Memos are bounded in bytes, so multi-byte characters count for more than one
+fn validate_memo(memo: &str) -> Result<()> {
+    require!(memo.len() <= MAX_MEMO_LEN, PaymentProcessorError::MemoTooLong);
+    Ok(())
+}
+
+// Current time for checks that cannot return an error, such as account constraints. If the clock
+// is unavailable every expiring entry is treated as expired.
+fn now_or_max() -> i64 {
+    Clock::get().map(|clock| clock.unix_timestamp).unwrap_or(i64::MAX)
+}
 
-        program_state.owner = owner;
-        program_state.admins = Vec::new();
-        program_state.bump = ctx.bumps.program_state;
+fn require_not_paused(state: &ProgramState, category: u32) -> Result<()> {
+    require!(
+        state.pause_mask & category == 0,
+        PaymentProcessorError::ProgramPaused
+    );
+    Ok(())
+}
 
-        msg!("Payment processor initialized with owner: {}", owner);
-        Ok(())
+// Signers skipping payment restrictions (limits, caps, user-level blocks) so operators can test in production.
+// Balance and accounting checks always apply.
+fn is_restriction_exempt(state: &ProgramState, signer: &Pubkey) -> bool {
+    state.owner == *signer || (state.admins_restriction_exempt && state.is_admin(signer))
+}
+
+// A user is paused while their `UserPause` PDA exists and the pause has not expired
+fn is_user_paused(user_pause: &AccountInfo, now: i64) -> Result<bool> {
+    if user_pause.owner != &crate::ID || user_pause.data_is_empty() {
+        return Ok(false);
     }
 
-    pub fn process_payment(ctx: Context<ProcessPayment>, amount: u64) -> Result<()> {
-        // Validate that the token program is either SPL Token or Token 2022
-        require!(
-            is_valid_token_program(&ctx.accounts.token_program.key()),
-            PaymentProcessorError::InvalidTokenProgram
-        );
+    let data = user_pause.try_borrow_data()?;
+    let user_pause = UserPause::try_deserialize(&mut &data[..])?;
+    Ok(now < user_pause.until_ts)
+}
+
+// Charges `amount` against the admin's `AdminBudget` PDA when one exists. The owner is never limited.
+fn charge_admin_budget(
+    program_state: &ProgramState,
+    authority: &Pubkey,
+    admin_budget: &AccountInfo,
+    amount: u64,
+    now: i64,
+) -> Result<()> {
+    if program_state.owner == *authority || admin_budget.owner != &crate::ID || admin_budget.data_is_empty() {
+        return Ok(());
+    }
+
+    let mut budget = {
+        let data = admin_budget.try_borrow_data()?;
+        AdminBudget::try_deserialize(&mut &data[..])?
+    };
+    budget.charge(amount, now)?;
+
+    let mut data = admin_budget.try_borrow_mut_data()?;
+    budget.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+#[derive(Debug)]
+struct TokenAccountData {
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    delegate: Option<Pubkey>,
+    delegated_amount: u64,
+}
+
+fn read_token_account(account: &AccountInfo) -> Result<TokenAccountData> {
+    let data = account.try_borrow_data()?;
+
+    // Pick the expected layout from the program owning the account
+    let valid_layout = if *account.owner == TOKEN_2022_PROGRAM_ID {
+        data.len() == TOKEN_ACCOUNT_LEN
+            || (data.len() > TOKEN_2022_ACCOUNT_TYPE_OFFSET
+                && data[TOKEN_2022_ACCOUNT_TYPE_OFFSET] == TOKEN_2022_ACCOUNT_TYPE_ACCOUNT)
+    } else {
+        data.len() == TOKEN_ACCOUNT_LEN
+    };
+    require!(valid_layout, PaymentProcessorError::MalformedTokenAccount);
 
-        // Validate user token account manually since it can be from either token program
+    // Mint is at bytes 0-32, owner at 32-64 and amount at 64-72 for both programs
+    let mint = Pubkey::try_from(&data[0..32]).map_err(|_| PaymentProcessorError::InvalidTokenAccount)?;
+    let owner = Pubkey::try_from(&data[32..64]).map_err(|_| PaymentProcessorError::InvalidTokenAccount)?;
+    let amount = u64::from_le_bytes(
+        <[u8; 8]>::try_from(&data[64..72]).map_err(|_| PaymentProcessorError::InvalidTokenAccount)?
+    );
+
+    // Delegate is a COption (u32 tag + key) at bytes 72-108, delegated amount at 121-129
+    let delegate = if data[72..76] == [1, 0, 0, 0] {
+        Some(Pubkey::try_from(&data[76..108]).map_err(|_| PaymentProcessorError::InvalidTokenAccount)?)
+    } else {
+        None
+    };
+    let delegated_amount = u64::from_le_bytes(
+        <[u8; 8]>::try_from(&data[121..129]).map_err(|_| PaymentProcessorError::InvalidTokenAccount)?
+    );
+
+    Ok(TokenAccountData { mint, owner, amount, delegate, delegated_amount })
+}
+
+// Rejects Token-2022 mints carrying an extension whose bit (1 << extension type) is set in
+// `blocked_extensions`. SPL Token mints and mints without extensions always pass.
+fn check_mint_extensions(token_mint: &AccountInfo, blocked_extensions: u64) -> Result<()> {
+    if blocked_extensions == 0 || *token_mint.owner != TOKEN_2022_PROGRAM_ID {
+        return Ok(());
+    }
+
+    let data = token_mint.try_borrow_data()?;
+    if data.len() <= TOKEN_2022_ACCOUNT_TYPE_OFFSET {
+        return Ok(());
+    }
+    require!(
+        data[TOKEN_2022_ACCOUNT_TYPE_OFFSET] == TOKEN_2022_ACCOUNT_TYPE_MINT,
+        PaymentProcessorError::InvalidTokenMint
+    );
+
+    // Each entry is extension type (u16) + value length (u16) + value
+    let mut offset = TOKEN_2022_TLV_OFFSET;
+    while offset + 4 <= data.len() {
+        let extension_type = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let length = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        // Type 0 marks the uninitialized space after the last extension
+        if extension_type == 0 {
+            break;
+        }
         require!(
-            ctx.accounts.user_token_account.owner == &ctx.accounts.token_program.key(),
-            PaymentProcessorError::InvalidTokenProgram
+            extension_type >= 64 || blocked_extensions & (1u64 << extension_type) == 0,
+            PaymentProcessorError::DisallowedMintExtension
         );
+        offset += 4 + length;
+    }
 
-        // Parse the token account data to validate mint and owner
-        {
-            let user_token_account_data = ctx.accounts.user_token_account.try_borrow_data()?;
-            require!(
-                user_token_account_data.len() >= 72, // Minimum size for both SPL Token and Token 2022 accounts
-                PaymentProcessorError::InvalidTokenAccount
-            );
+    Ok(())
+}
 
-            // For both SPL Token and Token 2022, the mint is at bytes 0-32 and owner is at bytes 32-64
-            let mint_bytes = &user_token_account_data[0..32];
-            let owner_bytes = &user_token_account_data[32..64];
+fn validate_user_token_account(
+    user_token_account: &AccountInfo,
+    user: &Pubkey,
+    token_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<()> {
+    // Validate that the token program is either SPL Token or Token 2022
+    require!(
+        is_valid_token_program(token_program),
+        PaymentProcessorError::InvalidTokenProgram
+    );
 
-            let user_token_mint = Pubkey::try_from(mint_bytes).map_err(|_| PaymentProcessorError::InvalidTokenAccount)?;
-            let user_token_owner = Pubkey::try_from(owner_bytes).map_err(|_| PaymentProcessorError::InvalidTokenAccount)?;
+    // Validate user token account manually since it can be from either token program
+    require!(
+        user_token_account.owner == token_program,
+        PaymentProcessorError::InvalidTokenProgram
+    );
 
-            require!(
-                user_token_mint == ctx.accounts.token_mint.key(),
-                PaymentProcessorError::InvalidTokenAccount
-            );
-            require!(
-                user_token_owner == ctx.accounts.user.key(),
-                PaymentProcessorError::InvalidTokenAccount
-            );
-        }
+    // Parse the token account data to validate mint and owner
+    let user_token_account = read_token_account(user_token_account)?;
+    require!(
+        user_token_account.mint == *token_mint,
+        PaymentProcessorError::InvalidTokenAccount
+    );
+    require!(
+        user_token_account.owner == *user,
+        PaymentProcessorError::InvalidTokenAccount
+    );
 
-        // Check if program token account needs initialization
-        let needs_initialization = {
-            let program_token_account_data = ctx.accounts.program_token_account.try_borrow_data()?;
-            program_token_account_data.len() == 0 || program_token_account_data[0] == 0
-        };
+    Ok(())
+}
 
-        if needs_initialization {
-            // Initialize the program token account
-            let initialize_account_ix = anchor_lang::solana_program::instruction::Instruction {
-                program_id: ctx.accounts.token_program.key(),
-                accounts: vec![
-                    anchor_lang::solana_program::instruction::AccountMeta::new(
-                        ctx.accounts.program_token_account.key(),
-                        false,
-                    ),
-                    anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
-                        ctx.accounts.token_mint.key(),
-                        false,
-                    ),
-                    anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
-                        ctx.accounts.program_token_account.key(),
-                        false,
-                    ),
-                    anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
-                        ctx.accounts.rent.key(),
-                        false,
-                    ),
-                ],
-                data: vec![1], // InitializeAccount instruction discriminator
-            };
+// Transfer fee a Token-2022 mint withholds from `amount` in `epoch`, zero for mints without one
+fn estimate_transfer_fee(token_mint: &AccountInfo, amount: u64, epoch: u64) -> Result<u64> {
+    if *token_mint.owner != TOKEN_2022_PROGRAM_ID {
+        return Ok(0);
+    }
 
-            anchor_lang::solana_program::program::invoke(
-                &initialize_account_ix,
-                &[
-                    ctx.accounts.program_token_account.to_account_info(),
-                    ctx.accounts.token_mint.to_account_info(),
-                    ctx.accounts.program_token_account.to_account_info(),
-                    ctx.accounts.rent.to_account_info(),
-                    ctx.accounts.token_program.to_account_info(),
-                ],
-            )?;
+    let data = token_mint.try_borrow_data()?;
+    if data.len() <= TOKEN_2022_ACCOUNT_TYPE_OFFSET {
+        return Ok(0);
+    }
+    require!(
+        data[TOKEN_2022_ACCOUNT_TYPE_OFFSET] == TOKEN_2022_ACCOUNT_TYPE_MINT,
+        PaymentProcessorError::InvalidTokenMint
+    );
 
-            msg!("Program token account initialized for mint: {}", ctx.accounts.token_mint.key());
-        } else {
-            // Validate existing program token account
+    let mut offset = TOKEN_2022_TLV_OFFSET;
+    while offset + 4 <= data.len() {
+        let extension_type = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let length = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        if extension_type == 0 {
+            break;
+        }
+        if extension_type == TOKEN_2022_EXTENSION_TRANSFER_FEE_CONFIG {
             require!(
-                ctx.accounts.program_token_account.owner == &ctx.accounts.token_program.key(),
-                PaymentProcessorError::InvalidTokenProgram
+                length == TRANSFER_FEE_CONFIG_LEN && offset + 4 + length <= data.len(),
+                PaymentProcessorError::InvalidTokenMint
             );
+            let config = &data[offset + 4..offset + 4 + length];
+            let read_u64 = |at: usize| u64::from_le_bytes(config[at..at + 8].try_into().unwrap());
 
-            let program_token_account_data = ctx.accounts.program_token_account.try_borrow_data()?;
-            require!(
-                program_token_account_data.len() >= 72,
-                PaymentProcessorError::InvalidTokenAccount
-            );
+            // The newer fee takes over from the epoch it was scheduled for
+            let fee_offset = if epoch >= read_u64(NEWER_TRANSFER_FEE_OFFSET) {
+                NEWER_TRANSFER_FEE_OFFSET
+            } else {
+                OLDER_TRANSFER_FEE_OFFSET
+            };
+            let maximum_fee = read_u64(fee_offset + 8);
+            let basis_points = u16::from_le_bytes([config[fee_offset + 16], config[fee_offset + 17]]);
 
-            let program_token_mint = Pubkey::try_from(&program_token_account_data[0..32])
-                .map_err(|_| PaymentProcessorError::InvalidTokenAccount)?;
-            
-            require!(
-                program_token_mint == ctx.accounts.token_mint.key(),
-                PaymentProcessorError::InvalidTokenAccount
-            );
+            // Token-2022 rounds the fee up, then caps it
+            return Ok(apply_bps(amount, basis_points, ROUNDING_CEIL).min(maximum_fee));
         }
+        offset += 4 + length;
+    }
+
+    Ok(0)
+}
+
+// A mint from another token program than the account would only surface as an opaque CPI failure,
+// so this runs before the program token account is created
+fn check_mint_token_program(token_mint: &AccountInfo, token_program: &AccountInfo) -> Result<()> {
+    require!(
+        token_mint.owner == &token_program.key(),
+        PaymentProcessorError::InvalidTokenProgram
+    );
+    Ok(())
+}
+
+fn prepare_program_token_account<'info>(
+    program_token_account: &AccountInfo<'info>,
+    token_mint: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    rent: &AccountInfo<'info>,
+) -> Result<()> {
+    // Check if program token account needs initialization
+    let needs_initialization = {
+        let program_token_account_data = program_token_account.try_borrow_data()?;
+        program_token_account_data.len() == 0 || program_token_account_data[0] == 0
+    };
 
-        // Create manual transfer instruction for Token-2022 compatibility
-        let transfer_ix = anchor_lang::solana_program::instruction::Instruction {
-            program_id: ctx.accounts.token_program.key(),
+    if needs_initialization {
+        // Initialize the program token account
+        let initialize_account_ix = anchor_lang::solana_program::instruction::Instruction {
+            program_id: token_program.key(),
             accounts: vec![
                 anchor_lang::solana_program::instruction::AccountMeta::new(
-                    ctx.accounts.user_token_account.key(),
+                    program_token_account.key(),
                     false,
                 ),
-                anchor_lang::solana_program::instruction::AccountMeta::new(
-                    ctx.accounts.program_token_account.key(),
+                anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
+                    token_mint.key(),
+                    false,
+                ),
+                anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
+                    program_token_account.key(),
                     false,
                 ),
                 anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
-                    ctx.accounts.user.key(),
-                    true,
+                    rent.key(),
+                    false,
                 ),
             ],
-            data: {
-                let mut data = vec![3]; // Transfer instruction discriminator
-                data.extend_from_slice(&amount.to_le_bytes());
-                data
-            },
+            data: vec![1], // InitializeAccount instruction discriminator
         };
 
         anchor_lang::solana_program::program::invoke(
-            &transfer_ix,
+            &initialize_account_ix,
             &[
-                ctx.accounts.user_token_account.to_account_info(),
-                ctx.accounts.program_token_account.to_account_info(),
-                ctx.accounts.user.to_account_info(),
-                ctx.accounts.token_program.to_account_info(),
+                program_token_account.clone(),
+                token_mint.clone(),
+                program_token_account.clone(),
+                rent.clone(),
+                token_program.clone(),
             ],
         )?;
 
-        emit!(PaymentEvent {
-            user: ctx.accounts.user.key(),
-            amount,
-            timestamp: Clock::get()?.unix_timestamp,
-            token_mint: ctx.accounts.token_mint.key(),
-        });
-
-        msg!("Payment processed: {} tokens from {}", amount, ctx.accounts.user.key());
-
-        Ok(())
-    }
-    
-    pub fn process_payment_sol(ctx: Context<ProcessPaymentSol>, amount: u64) -> Result<()> {
+        msg!("Program token account initialized for mint: {}", token_mint.key());
+    } else {
+        // Validate existing program token account
         require!(
-            ctx.accounts.user.lamports() >= amount,
-            PaymentProcessorError::InsufficientFunds
-        );
-        
-        let cpi_context = CpiContext::new(
-            ctx.accounts.system_program.to_account_info(),
-            anchor_lang::system_program::Transfer {
-                from: ctx.accounts.user.to_account_info(),
-                to: ctx.accounts.program_state.to_account_info(),
-            }
+            program_token_account.owner == &token_program.key(),
+            PaymentProcessorError::InvalidTokenProgram
         );
-        
-        anchor_lang::system_program::transfer(cpi_context, amount)?;
-        
-        emit!(SolPaymentEvent {
-            user: ctx.accounts.user.key(),
-            amount,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
 
-        msg!("SOL payment processed: {} lamports from {}", amount, ctx.accounts.user.key());
-        
-        Ok(())
+        let program_token_account = read_token_account(program_token_account)?;
+        require!(
+            program_token_account.mint == token_mint.key(),
+            PaymentProcessorError::InvalidTokenAccount
+        );
     }
 
-    pub fn add_admin(ctx: Context<AddAdmin>, new_admin: Pubkey) -> Result<()> {
-        let program_state = &mut ctx.accounts.program_state;
+    Ok(())
+}
 
-        require!(
-            !program_state.admins.contains(&new_admin),
-            PaymentProcessorError::AdminAlreadyExists
-        );
+// Adds a payment to the all-time processed volume of its mint
+fn record_volume(mint_state: &mut MintState, amount: u64, now: i64) -> Result<()> {
+    mint_state.open_day(now);
+    mint_state.total_processed = mint_state.total_processed
+        .checked_add(amount)
+        .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+    Ok(())
+}
 
-        program_state.admins.push(new_admin);
+// Mint decimals sit at the same offset for SPL Token and Token-2022 mints
+fn read_mint_decimals(mint: &AccountInfo) -> Result<u8> {
+    let data = mint.try_borrow_data()?;
+    require!(
+        data.len() >= MINT_LEN,
+        PaymentProcessorError::InvalidTokenMint
+    );
+    Ok(data[MINT_DECIMALS_OFFSET])
+}
 
-        msg!("Admin added: {}", new_admin);
-        Ok(())
-    }
+fn transfer_tokens<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let decimals = read_mint_decimals(mint)?;
 
-    pub fn remove_admin(ctx: Context<RemoveAdmin>, admin_to_remove: Pubkey) -> Result<()> {
-        let program_state = &mut ctx.accounts.program_state;
-        let admin_position = program_state.admins.iter().position(|&x| x == admin_to_remove);
+    // Create manual transfer instruction for Token-2022 compatibility. TransferChecked makes the token
+    // program verify the mint and its decimals, and is required for mints with transfer fees.
+    let transfer_ix = anchor_lang::solana_program::instruction::Instruction {
+        program_id: token_program.key(),
+        accounts: vec![
+            anchor_lang::solana_program::instruction::AccountMeta::new(
+                from.key(),
+                false,
+            ),
+            anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
+                mint.key(),
+                false,
+            ),
+            anchor_lang::solana_program::instruction::AccountMeta::new(
+                to.key(),
+                false,
+            ),
+            anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
+                authority.key(),
+                true,
+            ),
+        ],
+        data: {
+            let mut data = vec![12]; // TransferChecked instruction discriminator
+            data.extend_from_slice(&amount.to_le_bytes());
+            data.push(decimals);
+            data
+        },
+    };
 
-        require!(
-            admin_position.is_some(),
-            PaymentProcessorError::AdminNotFound
-        );
+    anchor_lang::solana_program::program::invoke_signed(
+        &transfer_ix,
+        &[
+            from.clone(),
+            mint.clone(),
+            to.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )?;
 
-        program_state.admins.remove(admin_position.unwrap());
+    Ok(())
+}
 
-        msg!("Admin removed: {}", admin_to_remove);
-        Ok(())
+// Withdraws `amount` from the program token account after the balance, reserve and destination checks.
+// Refunds return what users paid, so `outflow_limits` off skips the minimum withdrawal and the reserves.
+#[allow(clippy::too_many_arguments)]
+fn withdraw_tokens<'info>(
+    program_state: &ProgramState,
+    mint_state: &MintState,
+    token_program: &AccountInfo<'info>,
+    program_token_account: &AccountInfo<'info>,
+    destination_token_account: &AccountInfo<'info>,
+    token_mint: &AccountInfo<'info>,
+    amount: u64,
+    program_token_bump: u8,
+    outflow_limits: bool,
+) -> Result<()> {
+    require!(
+        !outflow_limits || amount >= mint_state.min_withdrawal,
+        PaymentProcessorError::WithdrawalTooSmall
+    );
+
+    // Validate that the token program is either SPL Token or Token 2022
+    require!(
+        is_valid_token_program(&token_program.key()),
+        PaymentProcessorError::InvalidTokenProgram
+    );
+
+    // Validate program token account manually
+    require!(
+        program_token_account.owner == &token_program.key(),
+        PaymentProcessorError::InvalidTokenProgram
+    );
+
+    let program_token_data = read_token_account(program_token_account)?;
+    require!(
+        program_token_data.amount >= amount,
+        PaymentProcessorError::InsufficientFunds
+    );
+
+    // Keep the configured share of the all-time processed volume in the program token account
+    require!(
+        !outflow_limits || program_token_data.amount - amount >= mint_state.required_reserve(program_state),
+        PaymentProcessorError::BelowReserve
+    );
+    require!(
+        !outflow_limits || program_token_data.amount - amount >= mint_state.min_token_balance,
+        PaymentProcessorError::BelowMinBalance
+    );
+
+    // Validate destination token account manually
+    require!(
+        destination_token_account.owner == &token_program.key(),
+        PaymentProcessorError::InvalidTokenProgram
+    );
+
+    let destination_token_data = read_token_account(destination_token_account)?;
+    require!(
+        destination_token_data.mint == token_mint.key(),
+        PaymentProcessorError::InvalidTokenAccount
+    );
+
+    let token_mint_key = token_mint.key();
+    let seeds = &[
+        b"program_token_account".as_ref(),
+        token_mint_key.as_ref(),
+        mint_state.program_token_seed(),
+        &[program_token_bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    transfer_tokens(
+        token_program,
+        program_token_account,
+        token_mint,
+        destination_token_account,
+        program_token_account,
+        amount,
+        signer,
+    )?;
+
+    let balance = program_token_data.amount - amount;
+    if balance < mint_state.low_balance_threshold {
+        emit!(LowBalanceEvent {
+            token_mint: token_mint_key,
+            balance,
+            threshold: mint_state.low_balance_threshold,
+        });
     }
 
-    pub fn change_owner(ctx: Context<ChangeOwner>, new_owner: Pubkey) -> Result<()> {
-        let program_state = &mut ctx.accounts.program_state;
-        let old_owner = program_state.owner;
-        
-        program_state.owner = new_owner;
+    Ok(())
+}
 
-        msg!("Owner changed from {} to {}", old_owner, new_owner);
-        Ok(())
+// When `require_kyc_destination` is set, only lets tokens out to token accounts whose owner has a
+// `KycRegistry` PDA
+fn check_kyc_destination(
+    program_state: &ProgramState,
+    destination_token_account: &AccountInfo,
+    kyc_registry: Option<&AccountInfo>,
+) -> Result<()> {
+    if !program_state.require_kyc_destination {
+        return Ok(());
     }
 
-    pub fn get_admins(ctx: Context<GetAdmins>) -> Result<Vec<Pubkey>> {
-        let program_state = &ctx.accounts.program_state;
-        Ok(program_state.admins.clone())
+    let destination_owner = read_token_account(destination_token_account)?.owner;
+    let (expected_registry, _bump) =
+        Pubkey::find_program_address(&[b"kyc".as_ref(), destination_owner.as_ref()], &crate::ID);
+    let registered = kyc_registry.is_some_and(|registry| {
+        registry.key() == expected_registry && registry.owner == &crate::ID && !registry.data_is_empty()
+    });
+    require!(registered, PaymentProcessorError::DestinationNotKyc);
+    Ok(())
+}
+
+// Post-operation program token balance for events, only read when `emit_balance_in_events` is set
+fn treasury_balance_for_event(program_state: &ProgramState, program_token_account: &AccountInfo) -> Result<Option<u64>> {
+    if !program_state.emit_balance_in_events {
+        return Ok(None);
     }
+    Ok(Some(read_token_account(program_token_account)?.amount))
+}
 
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        // Validate that the token program is either SPL Token or Token 2022
-        require!(
-            is_valid_token_program(&ctx.accounts.token_program.key()),
-            PaymentProcessorError::InvalidTokenProgram
-        );
+// Moves whatever the program token account holds above `hot_balance_target` (and the reserve) to
+// the cold treasury configured for the mint. Does nothing while `hot_balance_target` is zero.
+#[allow(clippy::too_many_arguments)]
+fn forward_to_cold_treasury<'info>(
+    program_state: &ProgramState,
+    mint_state: &MintState,
+    token_program: &AccountInfo<'info>,
+    program_token_account: &AccountInfo<'info>,
+    cold_treasury: Option<&AccountInfo<'info>>,
+    token_mint: &AccountInfo<'info>,
+    program_token_bump: u8,
+    timestamp: i64,
+) -> Result<()> {
+    if mint_state.hot_balance_target == 0 {
+        return Ok(());
+    }
 
-        // Validate program token account manually
-        require!(
-            ctx.accounts.program_token_account.owner == &ctx.accounts.token_program.key(),
-            PaymentProcessorError::InvalidTokenProgram
-        );
+    let balance = read_token_account(program_token_account)?.amount;
+    let retained = mint_state.hot_balance_target.max(mint_state.required_reserve(program_state));
+    if balance <= retained {
+        return Ok(());
+    }
+    let overflow = balance - retained;
 
-        {
-            let program_token_account_data = ctx.accounts.program_token_account.try_borrow_data()?;
-            require!(
-                program_token_account_data.len() >= 72,
-                PaymentProcessorError::InvalidTokenAccount
-            );
+    let token_mint_key = token_mint.key();
+    let cold_treasury = cold_treasury.ok_or(PaymentProcessorError::InvalidColdTreasury)?;
+    require!(
+        mint_state.cold_treasury == Some(cold_treasury.key())
+            && cold_treasury.owner == &token_program.key(),
+        PaymentProcessorError::InvalidColdTreasury
+    );
+    require!(
+        read_token_account(cold_treasury)?.mint == token_mint_key,
+        PaymentProcessorError::InvalidTokenAccount
+    );
 
-            // Parse token account data to get amount (at bytes 64-72)
-            let amount_bytes = &program_token_account_data[64..72];
-            let program_token_amount = u64::from_le_bytes(
-                amount_bytes.try_into().map_err(|_| PaymentProcessorError::InvalidTokenAccount)?
-            );
-            
-            require!(
-                program_token_amount >= amount,
-                PaymentProcessorError::InsufficientFunds
-            );
-        }
+    let seeds = &[
+        b"program_token_account".as_ref(),
+        token_mint_key.as_ref(),
+        mint_state.program_token_seed(),
+        &[program_token_bump],
+    ];
+    transfer_tokens(
+        token_program,
+        program_token_account,
+        token_mint,
+        cold_treasury,
+        program_token_account,
+        overflow,
+        &[&seeds[..]],
+    )?;
 
-        // Validate destination token account manually
-        require!(
-            ctx.accounts.destination_token_account.owner == &ctx.accounts.token_program.key(),
-            PaymentProcessorError::InvalidTokenProgram
-        );
+    emit!(ColdTransferEvent {
+        token_mint: token_mint_key,
+        cold_treasury: cold_treasury.key(),
+        amount: overflow,
+        timestamp,
+    });
 
-        {
-            let destination_token_account_data = ctx.accounts.destination_token_account.try_borrow_data()?;
-            require!(
-                destination_token_account_data.len() >= 72,
-                PaymentProcessorError::InvalidTokenAccount
-            );
+    Ok(())
+}
 
-            let destination_token_mint = Pubkey::try_from(&destination_token_account_data[0..32])
-                .map_err(|_| PaymentProcessorError::InvalidTokenAccount)?;
-            
-            require!(
-                destination_token_mint == ctx.accounts.token_mint.key(),
-                PaymentProcessorError::InvalidTokenAccount
-            );
-        }
+// Size of a token account for `token_mint`, including the extensions Token-2022 requires for it
+fn program_token_account_len<'info>(
+    token_program: &AccountInfo<'info>,
+    token_mint: &AccountInfo<'info>,
+) -> Result<usize> {
+    if token_program.key() != TOKEN_2022_PROGRAM_ID {
+        return Ok(TOKEN_ACCOUNT_LEN);
+    }
 
-        let token_mint_key = ctx.accounts.token_mint.key();
-        let seeds = &[
-            b"program_token_account",
-            token_mint_key.as_ref(),
-            &[ctx.bumps.program_token_account],
-        ];
-        let signer = &[&seeds[..]];
+    let get_size_ix = anchor_lang::solana_program::instruction::Instruction {
+        program_id: token_program.key(),
+        accounts: vec![
+            anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
+                token_mint.key(),
+                false,
+            ),
+        ],
+        data: vec![21], // GetAccountDataSize instruction discriminator
+    };
 
-        // Create manual transfer instruction for Token-2022 compatibility
-        let transfer_ix = anchor_lang::solana_program::instruction::Instruction {
-            program_id: ctx.accounts.token_program.key(),
-            accounts: vec![
-                anchor_lang::solana_program::instruction::AccountMeta::new(
-                    ctx.accounts.program_token_account.key(),
-                    false,
-                ),
-                anchor_lang::solana_program::instruction::AccountMeta::new(
-                    ctx.accounts.destination_token_account.key(),
-                    false,
-                ),
-                anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
-                    ctx.accounts.program_token_account.key(),
-                    true,
-                ),
-            ],
-            data: {
-                let mut data = vec![3]; // Transfer instruction discriminator
-                data.extend_from_slice(&amount.to_le_bytes());
-                data
-            },
-        };
+    anchor_lang::solana_program::program::invoke(
+        &get_size_ix,
+        &[token_mint.clone(), token_program.clone()],
+    )?;
 
-        anchor_lang::solana_program::program::invoke_signed(
-            &transfer_ix,
-            &[
-                ctx.accounts.program_token_account.to_account_info(),
-                ctx.accounts.destination_token_account.to_account_info(),
-                ctx.accounts.program_token_account.to_account_info(),
-                ctx.accounts.token_program.to_account_info(),
-            ],
-            signer,
-        )?;
+    let (returning_program, size_data) = anchor_lang::solana_program::program::get_return_data()
+        .ok_or(PaymentProcessorError::InvalidTokenMint)?;
+    require!(
+        returning_program == token_program.key(),
+        PaymentProcessorError::InvalidTokenMint
+    );
+    let size = u64::from_le_bytes(
+        <[u8; 8]>::try_from(&size_data[..]).map_err(|_| PaymentProcessorError::InvalidTokenMint)?
+    );
 
-        msg!("Withdrawal processed: {} tokens by {} to {}", 
-             amount, 
-             ctx.accounts.authority.key(), 
-             ctx.accounts.destination_token_account.key());
+    Ok(size as usize)
+}
 
-        Ok(())
+// Creates the program token account on the first payment for a mint, sized for the mint's required
+// extensions. Its rent comes out of the `init_subsidy` lamports held by program_state while they
+// last, and from `payer` otherwise. Returns whether the account was created.
+#[allow(clippy::too_many_arguments)]
+fn create_program_token_account<'info>(
+    program_state: &mut Account<'info, ProgramState>,
+    mint_state: &MintState,
+    payer: &AccountInfo<'info>,
+    program_token_account: &AccountInfo<'info>,
+    token_mint: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_token_bump: u8,
+) -> Result<bool> {
+    if !program_token_account.data_is_empty() {
+        return Ok(false);
     }
 
-    pub fn withdraw_sol(ctx: Context<WithdrawSol>, amount: u64) -> Result<()> {
-        let program_state_account = &ctx.accounts.program_state;
-        let rent = Rent::get()?;
-        let min_balance = rent.minimum_balance(program_state_account.to_account_info().data_len());
+    let rent = Rent::get()?;
+    let space = program_token_account_len(token_program, token_mint)?;
+    let missing_lamports = rent.minimum_balance(space).saturating_sub(program_token_account.lamports());
 
-        msg!("Program state balance: {}, Min balance needed: {}, Amount requested: {}", 
-            program_state_account.to_account_info().lamports(),
-            min_balance,
-            amount);
+    if missing_lamports > 0 && program_state.init_subsidy >= missing_lamports {
+        let program_state_info = program_state.to_account_info();
+        let available = program_state_info.lamports()
+            .saturating_sub(rent.minimum_balance(program_state_info.data_len()));
 
-        require!(
-            program_state_account.to_account_info().lamports() >= amount + min_balance,
-            PaymentProcessorError::InsufficientFunds
-        );
+        // Keep program_state rent exempt, the payer covers the rent if the subsidy is not actually funded
+        if available >= missing_lamports {
+            **program_state_info.try_borrow_mut_lamports()? -= missing_lamports;
+            **program_token_account.try_borrow_mut_lamports()? += missing_lamports;
+            program_state.init_subsidy -= missing_lamports;
 
-        let seeds = &[
-            b"program_state".as_ref(),
-            &[program_state_account.bump],
-        ];
-        let _signer = &[&seeds[..]];
+            emit!(InitSubsidyUsedEvent {
+                token_mint: token_mint.key(),
+                program_token_account: program_token_account.key(),
+                lamports: missing_lamports,
+                remaining_subsidy: program_state.init_subsidy,
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        }
+    }
 
-        **program_state_account.to_account_info().try_borrow_mut_lamports()? -= amount;
-        **ctx.accounts.destination.to_account_info().try_borrow_mut_lamports()? += amount;
+    let token_mint_key = token_mint.key();
+    let seeds = &[
+        b"program_token_account".as_ref(),
+        token_mint_key.as_ref(),
+        mint_state.program_token_seed(),
+        &[program_token_bump],
+    ];
 
-        msg!("SOL withdrawal processed: {} lamports by {} to {}", 
-             amount, 
-             ctx.accounts.authority.key(), 
-             ctx.accounts.destination.key());
+    create_pda_account(
+        payer,
+        program_token_account,
+        space,
+        &token_program.key(),
+        system_program,
+        &[&seeds[..]],
+    )?;
+    Ok(true)
+}
 
-        Ok(())
+// Creates a PDA owned by `owner`, also handling addresses that were already sent lamports
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    space: usize,
+    owner: &Pubkey,
+    system_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let required_lamports = Rent::get()?.minimum_balance(space);
+    let current_lamports = account.lamports();
+
+    if current_lamports == 0 {
+        anchor_lang::system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                anchor_lang::system_program::CreateAccount {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+                signer_seeds,
+            ),
+            required_lamports,
+            space as u64,
+            owner,
+        )?;
+    } else {
+        let top_up = required_lamports.saturating_sub(current_lamports);
+        if top_up > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    anchor_lang::system_program::Transfer {
+                        from: payer.clone(),
+                        to: account.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        anchor_lang::system_program::allocate(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                anchor_lang::system_program::Allocate {
+                    account_to_allocate: account.clone(),
+                },
+                signer_seeds,
+            ),
+            space as u64,
+        )?;
+        anchor_lang::system_program::assign(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                anchor_lang::system_program::Assign {
+                    account_to_assign: account.clone(),
+                },
+                signer_seeds,
+            ),
+            owner,
+        )?;
     }
+
+    Ok(())
 }
 
-#[account]
-pub struct ProgramState {
-    pub owner: Pubkey,
-    pub admins: Vec<Pubkey>,
-    pub bump: u8,
+// Accounts the payment instructions have in common
+struct PaymentAccounts<'a, 'info> {
+    user: &'a AccountInfo<'info>,
+    user_token_account: &'a AccountInfo<'info>,
+    program_token_account: &'a AccountInfo<'info>,
+    cold_treasury: Option<&'a AccountInfo<'info>>,
+    token_mint: &'a AccountInfo<'info>,
+    token_program: &'a AccountInfo<'info>,
+    system_program: &'a AccountInfo<'info>,
+    rent: &'a AccountInfo<'info>,
+    program_token_bump: u8,
+}
+
+struct ReceivedPayment {
+    // Credited to the mint, after the mint's transfer fee and without the account creation fee
+    received: u64,
+    account_creation_fee: u64,
+}
+
+// Transfers `amount` from the user to the program token account, creating that account on the first
+// payment for the mint, and records what actually arrived. Mints with a transfer fee deliver less than
+// `amount`, so the balance change is what gets credited.
+fn receive_payment<'info>(
+    program_state: &mut Account<'info, ProgramState>,
+    mint_state: &mut MintState,
+    public_stats: &mut PublicStats,
+    accounts: &PaymentAccounts<'_, 'info>,
+    amount: u64,
+    timestamp: i64,
+) -> Result<ReceivedPayment> {
+    validate_user_token_account(
+        accounts.user_token_account,
+        &accounts.user.key(),
+        &accounts.token_mint.key(),
+        &accounts.token_program.key(),
+    )?;
+    check_mint_extensions(accounts.token_mint, program_state.blocked_extensions)?;
+    check_mint_token_program(accounts.token_mint, accounts.token_program)?;
+    // A mutable Token-2022 mint could have its decimals changed under us
+    if let Some(expected_decimals) = mint_state.expected_decimals {
+        require!(
+            read_mint_decimals(accounts.token_mint)? == expected_decimals,
+            PaymentProcessorError::InvalidTokenMint
+        );
+    }
+
+    // The payment creating the program token account also pays the creation fee, if any
+    let account_creation_fee = if create_program_token_account(
+        program_state,
+        mint_state,
+        accounts.user,
+        accounts.program_token_account,
+        accounts.token_mint,
+        accounts.token_program,
+        accounts.system_program,
+        accounts.program_token_bump,
+    )? {
+        mint_state.account_creation_fee
+    } else {
+        0
+    };
+
+    prepare_program_token_account(
+        accounts.program_token_account,
+        accounts.token_mint,
+        accounts.token_program,
+        accounts.rent,
+    )?;
+
+    let balance_before = read_token_account(accounts.program_token_account)?.amount;
+    transfer_tokens(
+        accounts.token_program,
+        accounts.user_token_account,
+        accounts.token_mint,
+        accounts.program_token_account,
+        accounts.user,
+        amount
+            .checked_add(account_creation_fee)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?,
+        &[],
+    )?;
+
+    let received = read_token_account(accounts.program_token_account)?.amount
+        .checked_sub(balance_before)
+        .ok_or(PaymentProcessorError::ArithmeticOverflow)?
+        .saturating_sub(account_creation_fee);
+    require!(received > 0, PaymentProcessorError::NothingReceived);
+
+    record_volume(mint_state, received, timestamp)?;
+    public_stats.record_payment(received, Clock::get()?.slot)?;
+
+    forward_to_cold_treasury(
+        program_state,
+        mint_state,
+        accounts.token_program,
+        accounts.program_token_account,
+        accounts.cold_treasury,
+        accounts.token_mint,
+        accounts.program_token_bump,
+        timestamp,
+    )?;
+
+    Ok(ReceivedPayment { received, account_creation_fee })
+}
+
+fn record_day_summary(accounts: &mut FinalizeDay, token_mint: Pubkey, day: u32, fold_skipped_days: bool) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp;
+    require!(
+        i64::from(day) < timestamp / SECONDS_PER_DAY,
+        PaymentProcessorError::InvalidDay
+    );
+    require!(
+        accounts.day_summary.finalized_at == 0,
+        PaymentProcessorError::DayAlreadyFinalized
+    );
+
+    let previous_closing = if accounts.mint_state.last_finalized_day.is_some() {
+        let data = accounts.previous_day_summary.try_borrow_data()?;
+        let previous = DaySummary::try_deserialize(&mut &data[..])?;
+        Some((previous.total_processed, previous.total_refunded))
+    } else {
+        None
+    };
+    let (first_day, (opening_processed, opening_refunded), (closing_processed, closing_refunded)) =
+        accounts.mint_state.day_range_totals(previous_closing, day, fold_skipped_days)?;
+
+    let day_summary = &mut accounts.day_summary;
+    day_summary.token_mint = token_mint;
+    day_summary.day = day;
+    day_summary.first_day = first_day;
+    day_summary.volume = closing_processed
+        .checked_sub(opening_processed)
+        .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+    day_summary.refunded = closing_refunded
+        .checked_sub(opening_refunded)
+        .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+    day_summary.total_processed = closing_processed;
+    day_summary.total_refunded = closing_refunded;
+    day_summary.finalized_at = timestamp;
+    accounts.mint_state.last_finalized_day = Some(day);
+
+    emit!(DayFinalizedEvent {
+        token_mint,
+        day,
+        volume: day_summary.volume,
+        refunded: day_summary.refunded,
+        timestamp,
+        first_day,
+    });
+
+    msg!("Days {} to {} finalized for {} with a volume of {}", first_day, day, token_mint, day_summary.volume);
+    Ok(())
+}
+
+fn close_token_account<'info>(
+    token_program: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let close_ix = anchor_lang::solana_program::instruction::Instruction {
+        program_id: token_program.key(),
+        accounts: vec![
+            anchor_lang::solana_program::instruction::AccountMeta::new(
+                account.key(),
+                false,
+            ),
+            anchor_lang::solana_program::instruction::AccountMeta::new(
+                destination.key(),
+                false,
+            ),
+            anchor_lang::solana_program::instruction::AccountMeta::new_readonly(
+                authority.key(),
+                true,
+            ),
+        ],
+        data: vec![9], // CloseAccount instruction discriminator
+    };
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &close_ix,
+        &[
+            account.clone(),
+            destination.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+// Message the discount signer signs. The program id and mint keep a signature from being replayed
+// against another deployment or for a payment in another mint.
+fn discount_message(user: &Pubkey, token_mint: &Pubkey, amount: u64, discount_bps: u16, nonce: u64) -> Vec<u8> {
+    let mut message = Vec::with_capacity(DISCOUNT_MESSAGE_LEN);
+    message.extend_from_slice(crate::ID.as_ref());
+    message.extend_from_slice(user.as_ref());
+    message.extend_from_slice(token_mint.as_ref());
+    message.extend_from_slice(&amount.to_le_bytes());
+    message.extend_from_slice(&discount_bps.to_le_bytes());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message
+}
+
+// Checks that the instruction right before the current one is an Ed25519 program instruction verifying
+// `signature` by `signer` over exactly `message`, with all its offsets pointing into its own data.
+fn verify_ed25519_instruction(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, PaymentProcessorError::InvalidDiscountSignature);
+
+    let ed25519_ix = load_instruction_at_checked((current_index - 1) as usize, instructions_sysvar)?;
+    require!(
+        ed25519_ix.program_id == ed25519_program::ID && ed25519_ix.accounts.is_empty(),
+        PaymentProcessorError::InvalidDiscountSignature
+    );
+
+    // Layout: signature count (u8), padding (u8), then one offsets entry of seven u16 values
+    let data = &ed25519_ix.data;
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        PaymentProcessorError::InvalidDiscountSignature
+    );
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+
+    let signature_offset = read_u16(2) as usize;
+    let signature_instruction_index = read_u16(4);
+    let public_key_offset = read_u16(6) as usize;
+    let public_key_instruction_index = read_u16(8);
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let message_instruction_index = read_u16(14);
+
+    require!(
+        signature_instruction_index == u16::MAX
+            && public_key_instruction_index == u16::MAX
+            && message_instruction_index == u16::MAX,
+        PaymentProcessorError::InvalidDiscountSignature
+    );
+    require!(
+        data.get(public_key_offset..public_key_offset + 32) == Some(&signer.to_bytes()[..])
+            && data.get(signature_offset..signature_offset + 64) == Some(&signature[..])
+            && data.get(message_offset..message_offset + message_size) == Some(message),
+        PaymentProcessorError::InvalidDiscountSignature
+    );
+
+    Ok(())
+}
+
+#[program]
+pub mod libertai_payment_processor {
+    use super::*;
+
+    pub fn initialize(ctx: Context<Initialize>, owner: Pubkey) -> Result<()> {
+        let program_state = &mut ctx.accounts.program_state;
+
+        program_state.init(owner, ctx.bumps.program_state);
+        ctx.accounts.mint_state.init(ACCEPTED_MINT, ctx.bumps.mint_state);
+
+        msg!("Payment processor initialized with owner: {}", owner);
+        Ok(())
+    }
+
+    /// Same as `initialize`, but also applies the admins and settings from `config` in one transaction.
+    pub fn initialize_with_config(ctx: Context<InitializeWithConfig>, config: InitConfig) -> Result<()> {
+        require!(
+            config.owner != Pubkey::default(),
+            PaymentProcessorError::InvalidConfig
+        );
+        require!(
+            config.reserve_bps <= MAX_BPS,
+            PaymentProcessorError::InvalidBasisPoints
+        );
+        require!(
+            config.initial_admins.len() <= MAX_ADMINS,
+            PaymentProcessorError::TooManyAdmins
+        );
+        ProgramState::checked_space(config.initial_admins.len(), 1)?;
+        for (index, admin) in config.initial_admins.iter().enumerate() {
+            require!(
+                !config.initial_admins[..index].contains(admin),
+                PaymentProcessorError::AdminAlreadyExists
+            );
+        }
+
+        let program_state = &mut ctx.accounts.program_state;
+
+        program_state.init(config.owner, ctx.bumps.program_state);
+        program_state.admins = config.initial_admins
+            .into_iter()
+            .map(|key| AdminEntry { key, expires_ts: None, can_withdraw: true, can_manage: true })
+            .collect();
+        program_state.discount_signer = config.discount_signer;
+        program_state.reserve_bps = config.reserve_bps;
+        program_state.admins_restriction_exempt = config.admins_restriction_exempt;
+
+        let mint_state = &mut ctx.accounts.mint_state;
+        mint_state.init(ACCEPTED_MINT, ctx.bumps.mint_state);
+        mint_state.daily_volume_cap = config.daily_volume_cap;
+
+        msg!(
+            "Payment processor initialized with owner: {} and {} admins",
+            config.owner,
+            ctx.accounts.program_state.admins.len()
+        );
+        Ok(())
+    }
+
+    /// Rewrites a `program_state` still in the layout the program was first deployed with
+    /// (`owner`, `admins: Vec<Pubkey>`, `bump`) into the current one, and creates the `MintState` of
+    /// the accepted mint. Existing admins keep every permission and never expire, all new settings
+    /// start from their defaults. Only the owner recorded in the old state can run it, once.
+    pub fn migrate_program_state(ctx: Context<MigrateProgramState>) -> Result<()> {
+        let program_state_info = ctx.accounts.program_state.to_account_info();
+        let program_state = migrated_program_state(
+            &program_state_info.try_borrow_data()?,
+            &ctx.accounts.authority.key(),
+        )?;
+
+        let space = ProgramState::checked_space(program_state.admins.len(), program_state.accepted_mints.len())?;
+        let top_up = Rent::get()?.minimum_balance(space).saturating_sub(program_state_info.lamports());
+        if top_up > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: program_state_info.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        program_state_info.resize(space)?;
+        program_state.try_serialize(&mut &mut program_state_info.try_borrow_mut_data()?[..])?;
+
+        ctx.accounts.mint_state.init(ACCEPTED_MINT, ctx.bumps.mint_state);
+
+        msg!("Program state migrated with {} admins", program_state.admins.len());
+        Ok(())
+    }
+
+    /// Processes a payment of `amount` tokens. When a `reference_id` is given, a `PaymentReceipt`
+    /// is created for it, so retrying the same `(user, reference_id)` fails instead of charging twice.
+    /// `memo` is passed through to the `PaymentEvent` for matching the payment with an invoice.
+    pub fn process_payment(
+        ctx: Context<ProcessPayment>,
+        amount: u64,
+        reference_id: Option<u64>,
+        memo: String,
+    ) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_PAYMENTS)?;
+        validate_memo(&memo)?;
+        require!(
+            ctx.accounts.program_state.accepted_mints.contains(&ctx.accounts.token_mint.key()),
+            PaymentProcessorError::InvalidTokenMint
+        );
+        require!(
+            reference_id.is_some() == ctx.accounts.payment_receipt.is_some(),
+            PaymentProcessorError::InvalidPaymentReceipt
+        );
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        let exempt = is_restriction_exempt(&ctx.accounts.program_state, &ctx.accounts.user.key());
+        require!(
+            exempt || !is_user_paused(&ctx.accounts.user_pause, timestamp)?,
+            PaymentProcessorError::UserPaused
+        );
+        ctx.accounts.mint_state.track_daily_volume(amount, timestamp, !exempt)?;
+
+        let user = ctx.accounts.user.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let rent = ctx.accounts.rent.to_account_info();
+        let payment = receive_payment(
+            &mut ctx.accounts.program_state,
+            &mut ctx.accounts.mint_state,
+            &mut ctx.accounts.public_stats,
+            &PaymentAccounts {
+                user: &user,
+                user_token_account: &ctx.accounts.user_token_account,
+                program_token_account: &ctx.accounts.program_token_account,
+                cold_treasury: ctx.accounts.cold_treasury.as_deref(),
+                token_mint: &ctx.accounts.token_mint,
+                token_program: &ctx.accounts.token_program,
+                system_program: &system_program,
+                rent: &rent,
+                program_token_bump: ctx.bumps.program_token_account,
+            },
+            amount,
+            timestamp,
+        )?;
+
+        if let (Some(payment_receipt), Some(reference_id)) = (ctx.accounts.payment_receipt.as_mut(), reference_id) {
+            payment_receipt.user = ctx.accounts.user.key();
+            payment_receipt.reference_id = reference_id;
+            payment_receipt.amount = payment.received;
+            payment_receipt.timestamp = timestamp;
+        }
+
+        emit!(PaymentEvent {
+            user: ctx.accounts.user.key(),
+            amount: payment.received,
+            timestamp,
+            token_mint: ctx.accounts.token_mint.key(),
+            cumulative_total: ctx.accounts.mint_state.total_processed,
+            treasury_balance: treasury_balance_for_event(
+                &ctx.accounts.program_state,
+                &ctx.accounts.program_token_account,
+            )?,
+            memo,
+            account_creation_fee: payment.account_creation_fee,
+            credited_amount: payment.received,
+        });
+
+        msg!("Payment processed: {} tokens received for {} from {}", payment.received, amount, ctx.accounts.user.key());
+
+        Ok(())
+    }
+
+    /// Processes a payment discounted by `discount_bps`, authorized off-chain by the `discount_signer`.
+    /// The transaction must include an Ed25519 program instruction right before this one, verifying the
+    /// signer's signature over `program_id || user || token_mint || amount || discount_bps || nonce`
+    /// (little-endian integers).
+    /// The user is charged the discounted amount. The event reports the tokens received as `amount` and
+    /// the full `amount` to credit as `credited_amount`, less whatever the mint's transfer fee withheld.
+    pub fn process_payment_with_discount(
+        ctx: Context<ProcessPaymentWithDiscount>,
+        amount: u64,
+        discount_bps: u16,
+        nonce: u64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_PAYMENTS)?;
+        require!(
+            ctx.accounts.program_state.accepted_mints.contains(&ctx.accounts.token_mint.key()),
+            PaymentProcessorError::InvalidTokenMint
+        );
+
+        require!(
+            discount_bps <= MAX_BPS,
+            PaymentProcessorError::InvalidDiscount
+        );
+
+        let discount_signer = ctx.accounts.program_state.discount_signer
+            .ok_or(PaymentProcessorError::DiscountsDisabled)?;
+
+        let user_key = ctx.accounts.user.key();
+        let message = discount_message(&user_key, &ctx.accounts.token_mint.key(), amount, discount_bps, nonce);
+
+        verify_ed25519_instruction(
+            &ctx.accounts.instructions,
+            &discount_signer,
+            &message,
+            &signature,
+        )?;
+
+        let discount = apply_bps(amount, discount_bps, ctx.accounts.program_state.rounding_mode);
+        let charged_amount = amount - discount;
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        let exempt = is_restriction_exempt(&ctx.accounts.program_state, &user_key);
+        require!(
+            exempt || !is_user_paused(&ctx.accounts.user_pause, timestamp)?,
+            PaymentProcessorError::UserPaused
+        );
+        ctx.accounts.mint_state.track_daily_volume(charged_amount, timestamp, !exempt)?;
+
+        let user = ctx.accounts.user.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let rent = ctx.accounts.rent.to_account_info();
+        let payment = receive_payment(
+            &mut ctx.accounts.program_state,
+            &mut ctx.accounts.mint_state,
+            &mut ctx.accounts.public_stats,
+            &PaymentAccounts {
+                user: &user,
+                user_token_account: &ctx.accounts.user_token_account,
+                program_token_account: &ctx.accounts.program_token_account,
+                cold_treasury: ctx.accounts.cold_treasury.as_deref(),
+                token_mint: &ctx.accounts.token_mint,
+                token_program: &ctx.accounts.token_program,
+                system_program: &system_program,
+                rent: &rent,
+                program_token_bump: ctx.bumps.program_token_account,
+            },
+            charged_amount,
+            timestamp,
+        )?;
+
+        let discount_nonce = &mut ctx.accounts.discount_nonce;
+        discount_nonce.user = user_key;
+        discount_nonce.used_at = timestamp;
+
+        // The discount is granted on the full amount, only what the transfer fee withheld is not credited
+        let credited_amount = amount.saturating_sub(charged_amount.saturating_sub(payment.received));
+
+        emit!(PaymentEvent {
+            user: user_key,
+            amount: payment.received,
+            timestamp,
+            token_mint: ctx.accounts.token_mint.key(),
+            cumulative_total: ctx.accounts.mint_state.total_processed,
+            treasury_balance: treasury_balance_for_event(
+                &ctx.accounts.program_state,
+                &ctx.accounts.program_token_account,
+            )?,
+            memo: String::new(),
+            account_creation_fee: payment.account_creation_fee,
+            credited_amount,
+        });
+
+        emit!(DiscountAppliedEvent {
+            user: user_key,
+            nonce,
+            discount_bps,
+            amount,
+            charged_amount,
+            timestamp,
+        });
+
+        msg!(
+            "Discounted payment processed: {} tokens charged and {} received for {} from {}",
+            charged_amount,
+            payment.received,
+            amount,
+            user_key
+        );
+
+        Ok(())
+    }
+
+    pub fn set_reserve_bps(ctx: Context<UpdateConfig>, reserve_bps: u16) -> Result<()> {
+        require!(
+            reserve_bps <= MAX_BPS,
+            PaymentProcessorError::InvalidBasisPoints
+        );
+
+        ctx.accounts.program_state.reserve_bps = reserve_bps;
+
+        msg!("Reserve set to {} basis points of total processed volume", reserve_bps);
+        Ok(())
+    }
+
+    /// Keeps at most `hot_balance_target` in the program token account of `token_mint`, forwarding the
+    /// excess of each payment to the `cold_treasury` token account. A zero target disables forwarding.
+    pub fn set_treasury_split(
+        ctx: Context<UpdateMintConfig>,
+        token_mint: Pubkey,
+        hot_balance_target: u64,
+        cold_treasury: Option<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            hot_balance_target == 0 || cold_treasury.is_some(),
+            PaymentProcessorError::InvalidColdTreasury
+        );
+
+        let mint_state = &mut ctx.accounts.mint_state;
+        mint_state.hot_balance_target = hot_balance_target;
+        mint_state.cold_treasury = cold_treasury;
+
+        msg!("Hot balance target for {} set to {}", token_mint, hot_balance_target);
+        Ok(())
+    }
+
+    /// Lamports of program_state that may be spent on the rent of new program token accounts, so that
+    /// payers are not charged for it.
+    pub fn set_init_subsidy(ctx: Context<UpdateConfig>, init_subsidy: u64) -> Result<()> {
+        ctx.accounts.program_state.init_subsidy = init_subsidy;
+
+        msg!("Program token account init subsidy set to {} lamports", init_subsidy);
+        Ok(())
+    }
+
+    /// Includes the post-payment program token balance in `PaymentEvent`, at the cost of an extra account read.
+    pub fn set_emit_balance_in_events(ctx: Context<UpdateConfig>, emit_balance_in_events: bool) -> Result<()> {
+        ctx.accounts.program_state.emit_balance_in_events = emit_balance_in_events;
+
+        msg!("Balance in events set to {}", emit_balance_in_events);
+        Ok(())
+    }
+
+    /// Smallest amount of `token_mint` a single withdrawal may move, zero disables it.
+    pub fn set_min_withdrawal(ctx: Context<UpdateMintConfig>, token_mint: Pubkey, min_withdrawal: u64) -> Result<()> {
+        ctx.accounts.mint_state.min_withdrawal = min_withdrawal;
+
+        msg!("Minimum withdrawal for {} set to {}", token_mint, min_withdrawal);
+        Ok(())
+    }
+
+    /// Decimals `token_mint` must have for payments to be accepted, None accepts any.
+    pub fn set_expected_decimals(
+        ctx: Context<UpdateMintConfig>,
+        token_mint: Pubkey,
+        expected_decimals: Option<u8>,
+    ) -> Result<()> {
+        ctx.accounts.mint_state.expected_decimals = expected_decimals;
+
+        msg!("Expected decimals for {} set to {:?}", token_mint, expected_decimals);
+        Ok(())
+    }
+
+    /// Pauses every instruction category whose PAUSE_* bit is set in `pause_mask` and resumes the others.
+    pub fn set_pause_mask(ctx: Context<UpdateConfig>, pause_mask: u32) -> Result<()> {
+        let program_state = &mut ctx.accounts.program_state;
+        let previous_mask = program_state.pause_mask;
+        program_state.pause_mask = pause_mask;
+
+        emit!(PauseMaskSetEvent {
+            previous_mask,
+            pause_mask,
+            set_by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Pause mask set to {:#x}", pause_mask);
+        Ok(())
+    }
+
+    /// Incident switch for the owner and managing admins: stops payments while leaving withdrawals open to rescue funds.
+    pub fn pause(ctx: Context<TogglePause>) -> Result<()> {
+        ctx.accounts.program_state.pause_mask |= PAUSE_PAYMENTS;
+
+        emit!(PauseToggled {
+            paused: true,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Payments paused by {}", ctx.accounts.authority.key());
+        Ok(())
+    }
+
+    pub fn unpause(ctx: Context<TogglePause>) -> Result<()> {
+        ctx.accounts.program_state.pause_mask &= !PAUSE_PAYMENTS;
+
+        emit!(PauseToggled {
+            paused: false,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Payments unpaused by {}", ctx.accounts.authority.key());
+        Ok(())
+    }
+
+    /// Balance `withdraw` always leaves in the program token account of `token_mint`, zero disables it.
+    pub fn set_min_token_balance(ctx: Context<UpdateMintConfig>, token_mint: Pubkey, min_token_balance: u64) -> Result<()> {
+        ctx.accounts.mint_state.min_token_balance = min_token_balance;
+
+        msg!("Minimum token balance for {} set to {}", token_mint, min_token_balance);
+        Ok(())
+    }
+
+    /// Rounding used for basis point calculations (discounts and the reserve), see the ROUNDING_* constants.
+    pub fn set_rounding_mode(ctx: Context<UpdateConfig>, rounding_mode: u8) -> Result<()> {
+        require!(
+            rounding_mode <= ROUNDING_CEIL,
+            PaymentProcessorError::InvalidRoundingMode
+        );
+
+        ctx.accounts.program_state.rounding_mode = rounding_mode;
+
+        msg!("Rounding mode set to {}", rounding_mode);
+        Ok(())
+    }
+
+    /// Caps the volume of `token_mint` accepted per 24h window, zero disables the cap.
+    pub fn set_daily_volume_cap(ctx: Context<UpdateMintConfig>, token_mint: Pubkey, daily_volume_cap: u64) -> Result<()> {
+        ctx.accounts.mint_state.daily_volume_cap = daily_volume_cap;
+
+        msg!("Daily volume cap for {} set to {}", token_mint, daily_volume_cap);
+        Ok(())
+    }
+
+    pub fn set_admins_restriction_exempt(ctx: Context<UpdateConfig>, exempt: bool) -> Result<()> {
+        ctx.accounts.program_state.admins_restriction_exempt = exempt;
+
+        msg!("Admins restriction exemption set to {}", exempt);
+        Ok(())
+    }
+
+    /// Token-2022 extension types (as `1 << type` bits) that payments may not be made in.
+    pub fn set_blocked_extensions(ctx: Context<UpdateConfig>, blocked_extensions: u64) -> Result<()> {
+        ctx.accounts.program_state.blocked_extensions = blocked_extensions;
+
+        msg!("Blocked mint extensions set to {:#x}", blocked_extensions);
+        Ok(())
+    }
+
+    /// Tokens charged on top of the first payment in `token_mint`, when that payment creates its program token account.
+    pub fn set_account_creation_fee(
+        ctx: Context<UpdateMintConfig>,
+        token_mint: Pubkey,
+        account_creation_fee: u64,
+    ) -> Result<()> {
+        ctx.accounts.mint_state.account_creation_fee = account_creation_fee;
+
+        msg!("Account creation fee for {} set to {}", token_mint, account_creation_fee);
+        Ok(())
+    }
+
+    /// Approves the `token_mint` account, owned by the new program version, that `migrate_treasury` may send to.
+    pub fn set_migration_target(
+        ctx: Context<UpdateMintConfig>,
+        token_mint: Pubkey,
+        migration_target: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.mint_state.migration_target = migration_target;
+
+        match migration_target {
+            Some(target) => msg!("Migration target for {} set to: {}", token_mint, target),
+            None => msg!("Migration target for {} cleared", token_mint),
+        }
+        Ok(())
+    }
+
+    /// Withdrawals leaving the program token balance of `token_mint` below `low_balance_threshold`
+    /// emit a `LowBalanceEvent`, zero disables it.
+    pub fn set_low_balance_threshold(
+        ctx: Context<UpdateMintConfig>,
+        token_mint: Pubkey,
+        low_balance_threshold: u64,
+    ) -> Result<()> {
+        ctx.accounts.mint_state.low_balance_threshold = low_balance_threshold;
+
+        msg!("Low balance threshold for {} set to {}", token_mint, low_balance_threshold);
+        Ok(())
+    }
+
+    /// Restricts withdrawals to token accounts owned by someone registered with `add_kyc`.
+    pub fn set_require_kyc_destination(ctx: Context<UpdateConfig>, require_kyc_destination: bool) -> Result<()> {
+        ctx.accounts.program_state.require_kyc_destination = require_kyc_destination;
+
+        msg!("KYC'd withdrawal destinations required: {}", require_kyc_destination);
+        Ok(())
+    }
+
+    pub fn add_kyc(ctx: Context<AddKyc>, owner: Pubkey) -> Result<()> {
+        let kyc_registry = &mut ctx.accounts.kyc_registry;
+        kyc_registry.owner = owner;
+        kyc_registry.added_at = Clock::get()?.unix_timestamp;
+
+        msg!("KYC registered for {}", owner);
+        Ok(())
+    }
+
+    pub fn remove_kyc(_ctx: Context<RemoveKyc>, owner: Pubkey) -> Result<()> {
+        msg!("KYC removed for {}", owner);
+        Ok(())
+    }
+
+    pub fn set_discount_signer(ctx: Context<UpdateConfig>, discount_signer: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.program_state.discount_signer = discount_signer;
+
+        match discount_signer {
+            Some(signer) => msg!("Discount signer set to: {}", signer),
+            None => msg!("Discounts disabled"),
+        }
+        Ok(())
+    }
+    
+    pub fn process_payment_sol(ctx: Context<ProcessPaymentSol>, amount: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_PAYMENTS)?;
+
+        require!(
+            ctx.accounts.user.lamports() >= amount,
+            PaymentProcessorError::InsufficientFunds
+        );
+        
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.user.to_account_info(),
+                to: ctx.accounts.program_state.to_account_info(),
+            }
+        );
+        
+        anchor_lang::system_program::transfer(cpi_context, amount)?;
+        
+        emit!(SolPaymentEvent {
+            user: ctx.accounts.user.key(),
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("SOL payment processed: {} lamports from {}", amount, ctx.accounts.user.key());
+        
+        Ok(())
+    }
+
+    /// Blocks payments from `user` until `until_ts`. Passing a past timestamp lifts the pause.
+    pub fn pause_user(ctx: Context<PauseUser>, user: Pubkey, until_ts: i64) -> Result<()> {
+        let user_pause = &mut ctx.accounts.user_pause;
+        user_pause.user = user;
+        user_pause.until_ts = until_ts;
+
+        emit!(UserPausedEvent {
+            user,
+            until_ts,
+            paused_by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("User {} paused until {}", user, until_ts);
+        Ok(())
+    }
+
+    /// Limits how much of `token_mint` `admin` can withdraw per 24h window. Admins without a budget
+    /// for a mint are not limited in it.
+    pub fn set_admin_budget(ctx: Context<SetAdminBudget>, admin: Pubkey, token_mint: Pubkey, limit: u64) -> Result<()> {
+        ctx.accounts.admin_budget.limit = limit;
+
+        msg!("Withdrawal budget of admin {} for {} set to {}", admin, token_mint, limit);
+        Ok(())
+    }
+
+    /// Adds `new_admin` with the given permissions, optionally losing them once `expires_ts` is reached.
+    pub fn add_admin(
+        ctx: Context<AddAdmin>,
+        new_admin: Pubkey,
+        expires_ts: Option<i64>,
+        can_withdraw: bool,
+        can_manage: bool,
+    ) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_ADMIN_CHANGES)?;
+
+        let program_state = &mut ctx.accounts.program_state;
+
+        require!(
+            program_state.admins.len() < MAX_ADMINS,
+            PaymentProcessorError::TooManyAdmins
+        );
+        require!(
+            !program_state.admins.iter().any(|admin| admin.key == new_admin),
+            PaymentProcessorError::AdminAlreadyExists
+        );
+        let now = Clock::get()?.unix_timestamp;
+        if let Some(expires_ts) = expires_ts {
+            require!(
+                expires_ts > now,
+                PaymentProcessorError::InvalidAdminExpiry
+            );
+        }
+
+        let entry = AdminEntry { key: new_admin, expires_ts, can_withdraw, can_manage };
+        require!(
+            program_state.can_grant(&ctx.accounts.authority.key(), &entry, now),
+            PaymentProcessorError::PermissionNotHeld
+        );
+        program_state.admins.push(entry);
+        ProgramState::checked_space(program_state.admins.len(), program_state.accepted_mints.len())?;
+
+        emit!(AdminAddedEvent {
+            admin: new_admin,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Admin added: {}", new_admin);
+        Ok(())
+    }
+
+    pub fn remove_admin(ctx: Context<RemoveAdmin>, admin_to_remove: Pubkey) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_ADMIN_CHANGES)?;
+
+        let program_state = &mut ctx.accounts.program_state;
+        let admin_position = program_state.admins.iter().position(|admin| admin.key == admin_to_remove);
+
+        require!(
+            admin_position.is_some(),
+            PaymentProcessorError::AdminNotFound
+        );
+
+        program_state.admins.remove(admin_position.unwrap());
+
+        emit!(AdminRemovedEvent {
+            admin: admin_to_remove,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Admin removed: {}", admin_to_remove);
+        Ok(())
+    }
+
+    /// Accepts payments in `token_mint`, each accepted mint being received in its own program token account.
+    /// Mints carrying an extension blocked by `blocked_extensions` are rejected here as well as on payment.
+    pub fn add_accepted_mint(ctx: Context<AddAcceptedMint>) -> Result<()> {
+        let token_mint = ctx.accounts.token_mint.key();
+        require!(
+            is_valid_token_program(ctx.accounts.token_mint.owner),
+            PaymentProcessorError::InvalidTokenMint
+        );
+        read_mint_decimals(&ctx.accounts.token_mint)?;
+        check_mint_extensions(&ctx.accounts.token_mint, ctx.accounts.program_state.blocked_extensions)?;
+
+        let program_state = &mut ctx.accounts.program_state;
+
+        require!(
+            !program_state.accepted_mints.contains(&token_mint),
+            PaymentProcessorError::MintAlreadyAccepted
+        );
+        require!(
+            program_state.accepted_mints.len() < MAX_ACCEPTED_MINTS,
+            PaymentProcessorError::TooManyMints
+        );
+
+        program_state.accepted_mints.push(token_mint);
+        ProgramState::checked_space(program_state.admins.len(), program_state.accepted_mints.len())?;
+
+        // A mint accepted again keeps the settings and counters it had
+        let mint_state = &mut ctx.accounts.mint_state;
+        if mint_state.token_mint == Pubkey::default() {
+            mint_state.init(token_mint, ctx.bumps.mint_state);
+        }
+
+        emit!(AcceptedMintAddedEvent {
+            token_mint,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Accepted mint added: {}", token_mint);
+        Ok(())
+    }
+
+    /// Stops accepting payments in `token_mint`. Balances already received stay in its
+    /// program token account and can still be withdrawn from there, and its `MintState` is kept.
+    pub fn remove_accepted_mint(ctx: Context<RemoveAcceptedMint>, token_mint: Pubkey) -> Result<()> {
+        let program_state = &mut ctx.accounts.program_state;
+        let mint_position = program_state.accepted_mints.iter().position(|mint| *mint == token_mint);
+
+        require!(
+            mint_position.is_some(),
+            PaymentProcessorError::InvalidTokenMint
+        );
+
+        program_state.accepted_mints.remove(mint_position.unwrap());
+
+        emit!(AcceptedMintRemovedEvent {
+            token_mint,
+            by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Accepted mint removed: {}", token_mint);
+        Ok(())
+    }
+
+    /// Deprecated: reassigns the owner immediately, so a wrong key loses control of the program.
+    /// Use `propose_owner` and `accept_owner` instead.
+    pub fn change_owner(ctx: Context<ChangeOwner>, new_owner: Pubkey) -> Result<()> {
+        let program_state = &mut ctx.accounts.program_state;
+        let old_owner = program_state.owner;
+        
+        program_state.owner = new_owner;
+        program_state.pending_owner = None;
+
+        emit!(OwnerChangedEvent {
+            old_owner,
+            new_owner,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Owner changed from {} to {}", old_owner, new_owner);
+        Ok(())
+    }
+
+    /// First step of an ownership transfer, `new_owner` must then call `accept_owner`.
+    pub fn propose_owner(ctx: Context<ChangeOwner>, new_owner: Pubkey) -> Result<()> {
+        ctx.accounts.program_state.pending_owner = Some(new_owner);
+
+        msg!("Ownership transfer proposed to {}", new_owner);
+        Ok(())
+    }
+
+    pub fn accept_owner(ctx: Context<AcceptOwner>) -> Result<()> {
+        let program_state = &mut ctx.accounts.program_state;
+        let old_owner = program_state.owner;
+        let new_owner = ctx.accounts.new_owner.key();
+
+        program_state.owner = new_owner;
+        program_state.pending_owner = None;
+
+        emit!(OwnerChangedEvent {
+            old_owner,
+            new_owner,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Owner changed from {} to {}", old_owner, new_owner);
+        Ok(())
+    }
+
+    pub fn cancel_ownership_transfer(ctx: Context<ChangeOwner>) -> Result<()> {
+        ctx.accounts.program_state.pending_owner = None;
+
+        msg!("Ownership transfer cancelled");
+        Ok(())
+    }
+
+    /// Removes admins whose expiry has passed and shrinks `program_state`, refunding the freed rent.
+    pub fn prune_expired_admins(ctx: Context<PruneExpiredAdmins>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let program_state = &mut ctx.accounts.program_state;
+
+        program_state.admins.retain(|admin| {
+            if admin.is_active(now) {
+                return true;
+            }
+            emit!(AdminExpiredEvent {
+                admin: admin.key,
+                expired_at: admin.expires_ts.unwrap_or_default(),
+                timestamp: now,
+            });
+            false
+        });
+
+        msg!("Expired admins pruned, {} admins left", program_state.admins.len());
+        Ok(())
+    }
+
+    /// Admins that have not expired, with their permissions.
+    pub fn get_admins(ctx: Context<GetAdmins>) -> Result<Vec<AdminEntry>> {
+        let now = Clock::get()?.unix_timestamp;
+        let program_state = &ctx.accounts.program_state;
+        Ok(program_state.admins
+            .iter()
+            .filter(|admin| admin.is_active(now))
+            .cloned()
+            .collect())
+    }
+
+    /// Records the accounting counters of `token_mint` into its `PeriodCheckpoint` of `period_id`, so
+    /// reporting can diff two periods. Each period can only be checkpointed once per mint.
+    pub fn checkpoint(ctx: Context<Checkpoint>, token_mint: Pubkey, period_id: u64) -> Result<()> {
+        let checkpoint = &mut ctx.accounts.checkpoint;
+        require!(
+            checkpoint.recorded_at == 0,
+            PaymentProcessorError::CheckpointExists
+        );
+
+        let mint_state = &ctx.accounts.mint_state;
+        let timestamp = Clock::get()?.unix_timestamp;
+        checkpoint.token_mint = token_mint;
+        checkpoint.period_id = period_id;
+        checkpoint.total_processed = mint_state.total_processed;
+        checkpoint.total_refunded = mint_state.total_refunded;
+        checkpoint.total_sol_withdrawn = ctx.accounts.program_state.total_sol_withdrawn;
+        checkpoint.recorded_at = timestamp;
+
+        emit!(CheckpointEvent {
+            token_mint,
+            period_id,
+            total_processed: checkpoint.total_processed,
+            total_refunded: checkpoint.total_refunded,
+            total_sol_withdrawn: checkpoint.total_sol_withdrawn,
+            timestamp,
+        });
+
+        msg!("Checkpoint recorded for {} in period {}", token_mint, period_id);
+        Ok(())
+    }
+
+    /// Records the figures of `token_mint` for `day` (days since the Unix epoch) as the change in its
+    /// all-time counters between the opening of that day and of the next one. Days are finalized in
+    /// order once they have ended, and each can only be finalized once per mint.
+    pub fn finalize_day(ctx: Context<FinalizeDay>, token_mint: Pubkey, day: u32) -> Result<()> {
+        record_day_summary(ctx.accounts, token_mint, day, false)
+    }
+
+    /// Records every day after the last finalized one up to `day` as a single summary, for when the
+    /// totals at their boundaries are no longer available. Only the boundaries of the two latest
+    /// days with activity are kept, so `day` must be recent enough for its closing totals.
+    pub fn finalize_days_through(ctx: Context<FinalizeDay>, token_mint: Pubkey, day: u32) -> Result<()> {
+        record_day_summary(ctx.accounts, token_mint, day, true)
+    }
+
+    /// Rent-exempt minimum for `program_state` at its current size, which grows as admins are added.
+    pub fn get_program_state_rent(ctx: Context<ViewState>) -> Result<u64> {
+        Ok(Rent::get()?.minimum_balance(ctx.accounts.program_state.to_account_info().data_len()))
+    }
+
+    /// All-time amount of `token_mint` refunded to users, to be netted against `total_processed`.
+    pub fn get_total_refunded(ctx: Context<ViewMintState>, _token_mint: Pubkey) -> Result<u64> {
+        Ok(ctx.accounts.mint_state.total_refunded)
+    }
+
+    /// All-time volume of `token_mint` received by payments.
+    pub fn get_stats(ctx: Context<ViewMintState>, _token_mint: Pubkey) -> Result<u64> {
+        Ok(ctx.accounts.mint_state.total_processed)
+    }
+
+    /// Address of the program token account `process_payment` uses for `token_mint`,
+    /// including the mint's current seed version.
+    pub fn derive_program_token_account(ctx: Context<ViewMintState>, token_mint: Pubkey) -> Result<Pubkey> {
+        let (address, _bump) = Pubkey::find_program_address(
+            &[
+                b"program_token_account".as_ref(),
+                token_mint.as_ref(),
+                ctx.accounts.mint_state.program_token_seed(),
+            ],
+            ctx.program_id,
+        );
+        Ok(address)
+    }
+
+    /// All monetary counters of `token_mint` and the SOL withdrawn in one read, along with its current
+    /// program token balance.
+    pub fn get_accounting_snapshot(ctx: Context<GetAccountingSnapshot>) -> Result<AccountingSnapshot> {
+        // The program token account only exists once the first payment went through
+        let program_token_balance = if ctx.accounts.program_token_account.data_is_empty() {
+            0
+        } else {
+            require!(
+                is_valid_token_program(ctx.accounts.program_token_account.owner),
+                PaymentProcessorError::InvalidTokenProgram
+            );
+            read_token_account(&ctx.accounts.program_token_account)?.amount
+        };
+
+        let mint_state = &ctx.accounts.mint_state;
+        Ok(AccountingSnapshot {
+            total_processed: mint_state.total_processed,
+            total_refunded: mint_state.total_refunded,
+            total_sol_withdrawn: ctx.accounts.program_state.total_sol_withdrawn,
+            program_token_balance,
+        })
+    }
+
+    pub fn get_accepted_token_programs(_ctx: Context<GetAcceptedTokenPrograms>) -> Result<Vec<Pubkey>> {
+        Ok(ACCEPTED_TOKEN_PROGRAMS.to_vec())
+    }
+
+    /// What the program token account receives when `amount` of `token_mint` is paid, after the
+    /// mint's transfer fee for the current epoch. The program itself takes no fee.
+    pub fn quote_net(ctx: Context<QuoteNet>, amount: u64) -> Result<NetQuote> {
+        require!(
+            is_valid_token_program(ctx.accounts.token_mint.owner),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+
+        let transfer_fee_estimate = estimate_transfer_fee(&ctx.accounts.token_mint, amount, Clock::get()?.epoch)?;
+        Ok(NetQuote {
+            gross: amount,
+            transfer_fee_estimate,
+            net: amount.saturating_sub(transfer_fee_estimate),
+        })
+    }
+
+    /// Whether a discounted payment or idempotent withdrawal already consumed `nonce`, letting clients
+    /// tell a landed transaction from one that needs to be retried.
+    pub fn is_nonce_used(ctx: Context<IsNonceUsed>, kind: NonceKind, nonce: u64) -> Result<bool> {
+        let nonce_account = &ctx.accounts.nonce_account;
+        require_keys_eq!(
+            nonce_account.key(),
+            kind.address(nonce),
+            anchor_lang::error::ErrorCode::ConstraintSeeds
+        );
+        if nonce_account.owner != &crate::ID || nonce_account.data_is_empty() {
+            return Ok(false);
+        }
+
+        let data = nonce_account.try_borrow_data()?;
+        let used_at = match kind {
+            NonceKind::Discount => DiscountNonce::try_deserialize(&mut &data[..])?.used_at,
+            NonceKind::Withdraw { .. } => WithdrawNonce::try_deserialize(&mut &data[..])?.used_at,
+        };
+
+        msg!("Nonce {} used at {}", nonce, used_at);
+        Ok(used_at != 0)
+    }
+
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_WITHDRAWALS)?;
+
+        charge_admin_budget(
+            &ctx.accounts.program_state,
+            &ctx.accounts.authority.key(),
+            &ctx.accounts.admin_budget,
+            amount,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        check_kyc_destination(
+            &ctx.accounts.program_state,
+            &ctx.accounts.destination_token_account,
+            ctx.accounts.kyc_registry.as_deref(),
+        )?;
+
+        withdraw_tokens(
+            &ctx.accounts.program_state,
+            &ctx.accounts.mint_state,
+            &ctx.accounts.token_program,
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.destination_token_account,
+            &ctx.accounts.token_mint,
+            amount,
+            ctx.bumps.program_token_account,
+            true,
+        )?;
+
+        emit!(WithdrawalEvent {
+            authority: ctx.accounts.authority.key(),
+            destination: ctx.accounts.destination_token_account.key(),
+            amount,
+            token_mint: Some(ctx.accounts.token_mint.key()),
+            timestamp: Clock::get()?.unix_timestamp,
+            treasury_balance: treasury_balance_for_event(
+                &ctx.accounts.program_state,
+                &ctx.accounts.program_token_account,
+            )?,
+        });
+
+        msg!("Withdrawal processed: {} tokens by {} to {}", 
+             amount, 
+             ctx.accounts.authority.key(), 
+             ctx.accounts.destination_token_account.key());
+
+        Ok(())
+    }
+
+    /// `withdraw` that can be safely retried: the first call with a given `nonce` transfers, later ones
+    /// succeed without transferring, or fail with `NonceReused` when `fail_on_reuse` is set. Nonces are
+    /// scoped to the authority and mint, so admins can't consume each other's.
+    pub fn withdraw_idempotent(
+        ctx: Context<WithdrawIdempotent>,
+        amount: u64,
+        nonce: u64,
+        fail_on_reuse: bool,
+    ) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_WITHDRAWALS)?;
+
+        if ctx.accounts.withdraw_nonce.used_at != 0 {
+            require!(!fail_on_reuse, PaymentProcessorError::NonceReused);
+            msg!("Withdrawal nonce {} already used, skipping", nonce);
+            return Ok(());
+        }
+
+        charge_admin_budget(
+            &ctx.accounts.program_state,
+            &ctx.accounts.authority.key(),
+            &ctx.accounts.admin_budget,
+            amount,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        check_kyc_destination(
+            &ctx.accounts.program_state,
+            &ctx.accounts.destination_token_account,
+            ctx.accounts.kyc_registry.as_deref(),
+        )?;
+
+        withdraw_tokens(
+            &ctx.accounts.program_state,
+            &ctx.accounts.mint_state,
+            &ctx.accounts.token_program,
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.destination_token_account,
+            &ctx.accounts.token_mint,
+            amount,
+            ctx.bumps.program_token_account,
+            true,
+        )?;
+
+        let withdraw_nonce = &mut ctx.accounts.withdraw_nonce;
+        withdraw_nonce.authority = ctx.accounts.authority.key();
+        withdraw_nonce.amount = amount;
+        withdraw_nonce.used_at = Clock::get()?.unix_timestamp;
+
+        emit!(WithdrawalEvent {
+            authority: ctx.accounts.authority.key(),
+            destination: ctx.accounts.destination_token_account.key(),
+            amount,
+            token_mint: Some(ctx.accounts.token_mint.key()),
+            timestamp: ctx.accounts.withdraw_nonce.used_at,
+            treasury_balance: treasury_balance_for_event(
+                &ctx.accounts.program_state,
+                &ctx.accounts.program_token_account,
+            )?,
+        });
+
+        msg!("Withdrawal processed: {} tokens by {} to {} with nonce {}",
+             amount,
+             ctx.accounts.authority.key(),
+             ctx.accounts.destination_token_account.key(),
+             nonce);
+
+        Ok(())
+    }
+
+    /// Pulls `amount` back into the program token account from a token account that approved the
+    /// program token account as its delegate when it was paid out.
+    pub fn clawback(ctx: Context<Clawback>, amount: u64) -> Result<()> {
+        require!(
+            is_valid_token_program(&ctx.accounts.token_program.key()),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+        require!(
+            ctx.accounts.source_token_account.owner == &ctx.accounts.token_program.key()
+                && ctx.accounts.program_token_account.owner == &ctx.accounts.token_program.key(),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+
+        let token_mint_key = ctx.accounts.token_mint.key();
+        let source = read_token_account(&ctx.accounts.source_token_account)?;
+        require!(
+            source.mint == token_mint_key,
+            PaymentProcessorError::InvalidTokenAccount
+        );
+        require!(
+            source.delegate == Some(ctx.accounts.program_token_account.key())
+                && source.delegated_amount >= amount,
+            PaymentProcessorError::UnauthorizedAccess
+        );
+        require!(
+            source.amount >= amount,
+            PaymentProcessorError::InsufficientFunds
+        );
+
+        let seeds = &[
+            b"program_token_account".as_ref(),
+            token_mint_key.as_ref(),
+            ctx.accounts.mint_state.program_token_seed(),
+            &[ctx.bumps.program_token_account],
+        ];
+
+        transfer_tokens(
+            &ctx.accounts.token_program,
+            &ctx.accounts.source_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.program_token_account,
+            amount,
+            &[&seeds[..]],
+        )?;
+
+        emit!(ClawbackEvent {
+            source: ctx.accounts.source_token_account.key(),
+            source_owner: source.owner,
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Clawed back {} tokens from {}", amount, ctx.accounts.source_token_account.key());
+        Ok(())
+    }
+
+    /// Refunds `amounts[i]` to the token account passed as `remaining_accounts[i]`. When KYC'd
+    /// destinations are required, `remaining_accounts` holds `(token account, KycRegistry PDA)` pairs
+    /// instead. The whole batch must be covered by the program token balance, otherwise nothing is refunded.
+    pub fn refund_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefundBatch<'info>>,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_WITHDRAWALS | PAUSE_REFUNDS)?;
+
+        let accounts_per_refund = if ctx.accounts.program_state.require_kyc_destination { 2 } else { 1 };
+        require!(
+            !amounts.is_empty()
+                && amounts.len() <= MAX_REFUND_BATCH
+                && amounts.len() * accounts_per_refund == ctx.remaining_accounts.len(),
+            PaymentProcessorError::InvalidBatch
+        );
+        require!(
+            is_valid_token_program(&ctx.accounts.token_program.key()),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+        require!(
+            ctx.accounts.program_token_account.owner == &ctx.accounts.token_program.key(),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+        require!(
+            read_token_account(&ctx.accounts.program_token_account)?.amount >= total,
+            PaymentProcessorError::InsufficientFunds
+        );
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        charge_admin_budget(
+            &ctx.accounts.program_state,
+            &ctx.accounts.authority.key(),
+            &ctx.accounts.admin_budget,
+            total,
+            timestamp,
+        )?;
+        ctx.accounts.mint_state.record_refund(total, timestamp)?;
+
+        let refund_count = amounts.len();
+        for (refund_accounts, amount) in ctx.remaining_accounts.chunks(accounts_per_refund).zip(amounts) {
+            let user_token_account = &refund_accounts[0];
+            check_kyc_destination(&ctx.accounts.program_state, user_token_account, refund_accounts.get(1))?;
+
+            withdraw_tokens(
+                &ctx.accounts.program_state,
+                &ctx.accounts.mint_state,
+                &ctx.accounts.token_program,
+                &ctx.accounts.program_token_account,
+                user_token_account,
+                &ctx.accounts.token_mint,
+                amount,
+                ctx.bumps.program_token_account,
+                false,
+            )?;
+
+            emit!(RefundEvent {
+                to: read_token_account(user_token_account)?.owner,
+                amount,
+                timestamp,
+            });
+        }
+
+        msg!("Refund batch processed: {} tokens to {} accounts by {}", total, refund_count, ctx.accounts.authority.key());
+        Ok(())
+    }
+
+    /// Returns `amount` tokens from the program token account to `user_token_account`.
+    pub fn refund(ctx: Context<Refund>, amount: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_WITHDRAWALS | PAUSE_REFUNDS)?;
+
+        charge_admin_budget(
+            &ctx.accounts.program_state,
+            &ctx.accounts.authority.key(),
+            &ctx.accounts.admin_budget,
+            amount,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        check_kyc_destination(
+            &ctx.accounts.program_state,
+            &ctx.accounts.user_token_account,
+            ctx.accounts.kyc_registry.as_deref(),
+        )?;
+
+        withdraw_tokens(
+            &ctx.accounts.program_state,
+            &ctx.accounts.mint_state,
+            &ctx.accounts.token_program,
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.token_mint,
+            amount,
+            ctx.bumps.program_token_account,
+            false,
+        )?;
+        let timestamp = Clock::get()?.unix_timestamp;
+        ctx.accounts.mint_state.record_refund(amount, timestamp)?;
+
+        let user = read_token_account(&ctx.accounts.user_token_account)?.owner;
+        emit!(RefundEvent {
+            to: user,
+            amount,
+            timestamp,
+        });
+
+        msg!("Refund processed: {} tokens to {} by {}", amount, user, ctx.accounts.authority.key());
+        Ok(())
+    }
+
+    /// Moves `amount` tokens to the token account approved with `set_migration_target`, to hand the
+    /// treasury over to a new version of the program.
+    pub fn migrate_treasury(ctx: Context<MigrateTreasury>, amount: u64) -> Result<()> {
+        require!(
+            ctx.accounts.mint_state.migration_target == Some(ctx.accounts.destination_token_account.key()),
+            PaymentProcessorError::InvalidMigrationTarget
+        );
+        require!(
+            is_valid_token_program(&ctx.accounts.token_program.key()),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+        require!(
+            ctx.accounts.program_token_account.owner == &ctx.accounts.token_program.key(),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+        require!(
+            read_token_account(&ctx.accounts.program_token_account)?.amount >= amount,
+            PaymentProcessorError::InsufficientFunds
+        );
+
+        let token_mint_key = ctx.accounts.token_mint.key();
+        require!(
+            ctx.accounts.destination_token_account.owner == &ctx.accounts.token_program.key(),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+        require!(
+            read_token_account(&ctx.accounts.destination_token_account)?.mint == token_mint_key,
+            PaymentProcessorError::InvalidTokenAccount
+        );
+
+        let seeds = &[
+            b"program_token_account".as_ref(),
+            token_mint_key.as_ref(),
+            ctx.accounts.mint_state.program_token_seed(),
+            &[ctx.bumps.program_token_account],
+        ];
+        let signer = &[&seeds[..]];
+
+        transfer_tokens(
+            &ctx.accounts.token_program,
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.destination_token_account,
+            &ctx.accounts.program_token_account,
+            amount,
+            signer,
+        )?;
+
+        emit!(TreasuryMigratedEvent {
+            token_mint: token_mint_key,
+            destination: ctx.accounts.destination_token_account.key(),
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Treasury migration: {} tokens to {}", amount, ctx.accounts.destination_token_account.key());
+        Ok(())
+    }
+
+    /// Closes the program token account for `token_mint` and sends its rent to `receiver`.
+    /// With `sweep_then_close`, any remaining balance is first moved to `destination_token_account`,
+    /// otherwise the account must already be empty.
+    pub fn close_program_token_account(ctx: Context<CloseProgramTokenAccount>, sweep_then_close: bool) -> Result<()> {
+        require!(
+            is_valid_token_program(&ctx.accounts.token_program.key()),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+        require!(
+            ctx.accounts.program_token_account.owner == &ctx.accounts.token_program.key(),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+
+        let balance = read_token_account(&ctx.accounts.program_token_account)?.amount;
+
+        let token_mint_key = ctx.accounts.token_mint.key();
+        let seeds = &[
+            b"program_token_account".as_ref(),
+            token_mint_key.as_ref(),
+            ctx.accounts.mint_state.program_token_seed(),
+            &[ctx.bumps.program_token_account],
+        ];
+        let signer = &[&seeds[..]];
+        let timestamp = Clock::get()?.unix_timestamp;
+
+        if balance > 0 {
+            require!(sweep_then_close, PaymentProcessorError::TokenAccountNotEmpty);
+            require!(
+                ctx.accounts.mint_state.required_reserve(&ctx.accounts.program_state) == 0,
+                PaymentProcessorError::BelowReserve
+            );
+
+            let destination_token_account = ctx.accounts.destination_token_account
+                .as_ref()
+                .ok_or(PaymentProcessorError::MissingSweepDestination)?;
+            require!(
+                destination_token_account.owner == &ctx.accounts.token_program.key(),
+                PaymentProcessorError::InvalidTokenProgram
+            );
+            require!(
+                read_token_account(destination_token_account)?.mint == token_mint_key,
+                PaymentProcessorError::InvalidTokenAccount
+            );
+
+            transfer_tokens(
+                &ctx.accounts.token_program,
+                &ctx.accounts.program_token_account,
+                &ctx.accounts.token_mint,
+                destination_token_account,
+                &ctx.accounts.program_token_account,
+                balance,
+                signer,
+            )?;
+
+            emit!(SweepEvent {
+                token_mint: token_mint_key,
+                destination: destination_token_account.key(),
+                amount: balance,
+                timestamp,
+            });
+
+            msg!("Swept {} tokens to {}", balance, destination_token_account.key());
+        }
+
+        close_token_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.receiver,
+            &ctx.accounts.program_token_account,
+            signer,
+        )?;
+
+        emit!(TokenAccountClosedEvent {
+            token_mint: token_mint_key,
+            receiver: ctx.accounts.receiver.key(),
+            timestamp,
+        });
+
+        msg!("Program token account closed for mint: {}", token_mint_key);
+
+        Ok(())
+    }
+
+    /// Moves the balance of a stray token account whose authority is the canonical program token account
+    /// (e.g. an ATA created for the PDA by mistake) into the canonical account, then closes it.
+    pub fn recover_noncanonical_account(ctx: Context<RecoverNoncanonicalAccount>) -> Result<()> {
+        require!(
+            is_valid_token_program(&ctx.accounts.token_program.key()),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+        require!(
+            ctx.accounts.source_token_account.key() != ctx.accounts.program_token_account.key()
+                && ctx.accounts.source_token_account.owner == &ctx.accounts.token_program.key()
+                && ctx.accounts.program_token_account.owner == &ctx.accounts.token_program.key(),
+            PaymentProcessorError::InvalidTokenAccount
+        );
+
+        let token_mint_key = ctx.accounts.token_mint.key();
+        let source = read_token_account(&ctx.accounts.source_token_account)?;
+        require!(
+            source.mint == token_mint_key && source.owner == ctx.accounts.program_token_account.key(),
+            PaymentProcessorError::InvalidTokenAccount
+        );
+
+        let seeds = &[
+            b"program_token_account".as_ref(),
+            token_mint_key.as_ref(),
+            ctx.accounts.mint_state.program_token_seed(),
+            &[ctx.bumps.program_token_account],
+        ];
+        let signer = &[&seeds[..]];
+
+        if source.amount > 0 {
+            transfer_tokens(
+                &ctx.accounts.token_program,
+                &ctx.accounts.source_token_account,
+                &ctx.accounts.token_mint,
+                &ctx.accounts.program_token_account,
+                &ctx.accounts.program_token_account,
+                source.amount,
+                signer,
+            )?;
+        }
+
+        close_token_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.source_token_account,
+            &ctx.accounts.receiver,
+            &ctx.accounts.program_token_account,
+            signer,
+        )?;
+
+        emit!(RecoveryEvent {
+            token_mint: token_mint_key,
+            source: ctx.accounts.source_token_account.key(),
+            amount: source.amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Recovered {} tokens from {}", source.amount, ctx.accounts.source_token_account.key());
+        Ok(())
+    }
+
+    /// Moves the program token account of `token_mint` to the PDA derived with `new_seed_version`, transferring
+    /// the whole balance and closing the old account, then records the version in the mint's `MintState` so
+    /// later instructions derive the new PDA. Other mints keep their own seed version.
+    pub fn reseed_program_token_account(ctx: Context<ReseedProgramTokenAccount>, new_seed_version: u8) -> Result<()> {
+        require!(
+            new_seed_version > ctx.accounts.mint_state.program_token_seed_version,
+            PaymentProcessorError::InvalidSeedVersion
+        );
+        require!(
+            is_valid_token_program(&ctx.accounts.token_program.key()),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+        require!(
+            ctx.accounts.old_program_token_account.owner == &ctx.accounts.token_program.key(),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+
+        let balance = read_token_account(&ctx.accounts.old_program_token_account)?.amount;
+
+        check_mint_token_program(&ctx.accounts.token_mint, &ctx.accounts.token_program)?;
+        prepare_program_token_account(
+            &ctx.accounts.new_program_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+            &ctx.accounts.rent.to_account_info(),
+        )?;
+
+        let token_mint_key = ctx.accounts.token_mint.key();
+        let seeds = &[
+            b"program_token_account".as_ref(),
+            token_mint_key.as_ref(),
+            ctx.accounts.mint_state.program_token_seed(),
+            &[ctx.bumps.old_program_token_account],
+        ];
+        let signer = &[&seeds[..]];
+
+        if balance > 0 {
+            transfer_tokens(
+                &ctx.accounts.token_program,
+                &ctx.accounts.old_program_token_account,
+                &ctx.accounts.token_mint,
+                &ctx.accounts.new_program_token_account,
+                &ctx.accounts.old_program_token_account,
+                balance,
+                signer,
+            )?;
+        }
+
+        close_token_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.old_program_token_account,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.old_program_token_account,
+            signer,
+        )?;
+
+        ctx.accounts.mint_state.program_token_seed_version = new_seed_version;
+
+        emit!(ProgramTokenAccountReseededEvent {
+            token_mint: token_mint_key,
+            old_account: ctx.accounts.old_program_token_account.key(),
+            new_account: ctx.accounts.new_program_token_account.key(),
+            seed_version: new_seed_version,
+            amount: balance,
+        });
+
+        msg!("Program token account for {} moved to seed version {}", token_mint_key, new_seed_version);
+        Ok(())
+    }
+
+    /// Creates and initializes the program token account for the accepted mint ahead of any payment,
+    /// sized for the mint's required Token-2022 extensions.
+    pub fn initialize_program_token_account(ctx: Context<InitializeProgramTokenAccount>) -> Result<()> {
+        require!(
+            is_valid_token_program(&ctx.accounts.token_program.key()),
+            PaymentProcessorError::InvalidTokenProgram
+        );
+        check_mint_token_program(&ctx.accounts.token_mint, &ctx.accounts.token_program)?;
+        require!(
+            ctx.accounts.program_token_account.data_is_empty(),
+            PaymentProcessorError::ProgramTokenAccountAlreadyInitialized
+        );
+
+        let space = program_token_account_len(&ctx.accounts.token_program, &ctx.accounts.token_mint)?;
+
+        let token_mint_key = ctx.accounts.token_mint.key();
+        let seeds = &[
+            b"program_token_account".as_ref(),
+            token_mint_key.as_ref(),
+            ctx.accounts.mint_state.program_token_seed(),
+            &[ctx.bumps.program_token_account],
+        ];
+        let signer = &[&seeds[..]];
+
+        create_pda_account(
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.program_token_account,
+            space,
+            &ctx.accounts.token_program.key(),
+            &ctx.accounts.system_program.to_account_info(),
+            signer,
+        )?;
+
+        prepare_program_token_account(
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+            &ctx.accounts.rent.to_account_info(),
+        )?;
+
+        emit!(ProgramTokenAccountInitializedEvent {
+            token_mint: token_mint_key,
+            program_token_account: ctx.accounts.program_token_account.key(),
+            token_program: ctx.accounts.token_program.key(),
+            space: space as u64,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_sol(ctx: Context<WithdrawSol>, amount: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.program_state, PAUSE_WITHDRAWALS)?;
+
+        let program_state_account = &ctx.accounts.program_state;
+        let rent = Rent::get()?;
+        let min_balance = rent.minimum_balance(program_state_account.to_account_info().data_len());
+
+        msg!("Program state balance: {}, Min balance needed: {}, Amount requested: {}", 
+            program_state_account.to_account_info().lamports(),
+            min_balance,
+            amount);
+
+        let required = amount
+            .checked_add(min_balance)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+        require!(
+            program_state_account.to_account_info().lamports() >= required,
+            PaymentProcessorError::InsufficientFunds
+        );
+
+        let seeds = &[
+            b"program_state".as_ref(),
+            &[program_state_account.bump],
+        ];
+        let _signer = &[&seeds[..]];
+
+        let source_info = program_state_account.to_account_info();
+        let mut source_lamports = source_info.try_borrow_mut_lamports()?;
+        **source_lamports = source_lamports
+            .checked_sub(amount)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+        drop(source_lamports);
+
+        let destination_info = ctx.accounts.destination.to_account_info();
+        let mut destination_lamports = destination_info.try_borrow_mut_lamports()?;
+        **destination_lamports = destination_lamports
+            .checked_add(amount)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+        drop(destination_lamports);
+
+        ctx.accounts.program_state.total_sol_withdrawn = ctx.accounts.program_state.total_sol_withdrawn
+            .checked_add(amount)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+
+        emit!(WithdrawalEvent {
+            authority: ctx.accounts.authority.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            token_mint: None,
+            timestamp: Clock::get()?.unix_timestamp,
+            treasury_balance: None,
+        });
+
+        msg!("SOL withdrawal processed: {} lamports by {} to {}", 
+             amount, 
+             ctx.accounts.authority.key(), 
+             ctx.accounts.destination.key());
+
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct ProgramState {
+    pub owner: Pubkey,
+    pub admins: Vec<AdminEntry>,
+    pub bump: u8,
+    pub discount_signer: Option<Pubkey>,
+    pub reserve_bps: u16,
+    pub admins_restriction_exempt: bool,
+    pub blocked_extensions: u64,
+    pub rounding_mode: u8,
+    pub total_sol_withdrawn: u64,
+    pub init_subsidy: u64,
+    pub emit_balance_in_events: bool,
+    pub pause_mask: u32,
+    pub pending_owner: Option<Pubkey>,
+    pub accepted_mints: Vec<Pubkey>,
+    pub require_kyc_destination: bool,
+}
+
+/// Settings and counters of one accepted mint. Amounts of different mints cannot be added up or
+/// compared, so everything denominated in tokens is kept here rather than in ProgramState.
+#[account]
+#[derive(Default)]
+pub struct MintState {
+    pub token_mint: Pubkey,
+    pub bump: u8,
+    pub program_token_seed_version: u8,
+    pub total_processed: u64,
+    pub total_refunded: u64,
+    pub daily_volume_cap: u64,
+    pub volume_window_start: i64,
+    pub volume_in_window: u64,
+    pub hot_balance_target: u64,
+    pub cold_treasury: Option<Pubkey>,
+    pub min_token_balance: u64,
+    pub min_withdrawal: u64,
+    pub account_creation_fee: u64,
+    pub migration_target: Option<Pubkey>,
+    pub low_balance_threshold: u64,
+    // Latest day with a payment or refund, and the all-time counters when it opened
+    pub day_open: u32,
+    pub day_open_processed: u64,
+    pub day_open_refunded: u64,
+    // Same for the day with activity before it, so finalize_day can lag a day behind
+    pub previous_day_open: u32,
+    pub previous_day_open_processed: u64,
+    pub previous_day_open_refunded: u64,
+    pub last_finalized_day: Option<u32>,
+    // Decimals payments require the mint to still have, unchecked when None
+    pub expected_decimals: Option<u8>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AccountingSnapshot {
+    pub total_processed: u64,
+    pub total_refunded: u64,
+    pub total_sol_withdrawn: u64,
+    pub program_token_balance: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NetQuote {
+    pub gross: u64,
+    pub transfer_fee_estimate: u64,
+    pub net: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub enum NonceKind {
+    Discount,
+    // Withdrawal nonces are scoped to the withdrawing authority and mint
+    Withdraw { authority: Pubkey, token_mint: Pubkey },
+}
+
+impl NonceKind {
+    pub fn address(&self, nonce: u64) -> Pubkey {
+        let nonce = nonce.to_le_bytes();
+        let (address, _bump) = match self {
+            NonceKind::Discount => Pubkey::find_program_address(&[b"discount_nonce".as_ref(), nonce.as_ref()], &crate::ID),
+            NonceKind::Withdraw { authority, token_mint } => Pubkey::find_program_address(
+                &[b"withdraw_nonce".as_ref(), authority.as_ref(), token_mint.as_ref(), nonce.as_ref()],
+                &crate::ID,
+            ),
+        };
+        address
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AdminEntry {
+    pub key: Pubkey,
+    // Admin rights end at this timestamp, None never expires
+    pub expires_ts: Option<i64>,
+    // Withdrawals, SOL withdrawals and refunds
+    pub can_withdraw: bool,
+    // Admin set, pausing and user pauses
+    pub can_manage: bool,
+}
+
+impl AdminEntry {
+    pub const LEN: usize = 32 + 9 + 1 + 1; // key + expires_ts + can_withdraw + can_manage
+
+    pub fn is_active(&self, now: i64) -> bool {
+        self.expires_ts.is_none_or(|expires_ts| now < expires_ts)
+    }
+}
+
+// get_admins returns every admin, the vec length and entries must fit in the return data
+const _: () = assert!(4 + MAX_ADMINS * AdminEntry::LEN <= anchor_lang::solana_program::program::MAX_RETURN_DATA);
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct InitConfig {
+    pub owner: Pubkey,
+    pub initial_admins: Vec<Pubkey>,
+    pub discount_signer: Option<Pubkey>,
+    pub reserve_bps: u16,
+    pub daily_volume_cap: u64,
+    pub admins_restriction_exempt: bool,
 }
 
 impl ProgramState {
-    pub const INITIAL_LEN: usize = 32 + 4 + 1 + 8; // owner + vec length + bump + discriminator
+    // owner + vec length + bump + discount signer + reserve bps + admins exemption + blocked extensions
+    // + rounding mode + total SOL withdrawn + init subsidy + balance in events + pause mask + pending owner
+    // + accepted mints vec length + KYC'd destinations + discriminator
+    pub const INITIAL_LEN: usize = 32 + 4 + 1 + 33 + 2 + 1 + 8 + 1 + 8 + 8 + 1 + 4 + 33 + 4 + 1 + 8;
+
+    /// Account size needed to hold `admin_count` admins and `mint_count` accepted mints
+    pub const fn space(admin_count: usize, mint_count: usize) -> usize {
+        Self::INITIAL_LEN + admin_count * AdminEntry::LEN + mint_count * 32
+    }
+
+    /// Same as `space`, failing when the account would outgrow MAX_PROGRAM_STATE_SIZE
+    pub fn checked_space(admin_count: usize, mint_count: usize) -> Result<usize> {
+        let space = Self::space(admin_count, mint_count);
+        require!(space <= MAX_PROGRAM_STATE_SIZE, PaymentProcessorError::ProgramStateTooLarge);
+        Ok(space)
+    }
+
+    pub fn init(&mut self, owner: Pubkey, bump: u8) {
+        self.owner = owner;
+        self.admins = Vec::new();
+        self.bump = bump;
+        self.discount_signer = None;
+        self.reserve_bps = 0;
+        self.admins_restriction_exempt = false;
+        self.blocked_extensions = 0;
+        self.rounding_mode = ROUNDING_FLOOR;
+        self.total_sol_withdrawn = 0;
+        self.init_subsidy = 0;
+        self.emit_balance_in_events = false;
+        self.pause_mask = 0;
+        self.pending_owner = None;
+        self.accepted_mints = vec![ACCEPTED_MINT];
+        self.require_kyc_destination = false;
+    }
+
+    pub fn is_admin(&self, pubkey: &Pubkey) -> bool {
+        let now = now_or_max();
+        self.admins.iter().any(|admin| admin.key == *pubkey && admin.is_active(now))
+    }
+
+    pub fn active_admin_count(&self) -> usize {
+        let now = now_or_max();
+        self.admins.iter().filter(|admin| admin.is_active(now)).count()
+    }
+    
+    // The owner holds every permission
+    fn has_permission(&self, pubkey: &Pubkey, permission: impl Fn(&AdminEntry) -> bool) -> bool {
+        let now = now_or_max();
+        self.owner == *pubkey
+            || self.admins.iter().any(|admin| admin.key == *pubkey && admin.is_active(now) && permission(admin))
+    }
+
+    pub fn can_withdraw(&self, pubkey: &Pubkey) -> bool {
+        self.has_permission(pubkey, |admin| admin.can_withdraw)
+    }
+
+    pub fn can_manage(&self, pubkey: &Pubkey) -> bool {
+        self.has_permission(pubkey, |admin| admin.can_manage)
+    }
+
+    /// Whether `granter` may add `entry`. The owner grants anything, an admin only the permissions
+    /// it holds itself and an expiry no later than its own.
+    pub fn can_grant(&self, granter: &Pubkey, entry: &AdminEntry, now: i64) -> bool {
+        if self.owner == *granter {
+            return true;
+        }
+
+        self.admins.iter().any(|admin| {
+            admin.key == *granter
+                && admin.is_active(now)
+                && admin.can_manage
+                && (admin.can_withdraw || !entry.can_withdraw)
+                && admin.expires_ts.is_none_or(|expires_ts| {
+                    entry.expires_ts.is_some_and(|entry_expires_ts| entry_expires_ts <= expires_ts)
+                })
+        })
+    }
+}
+
+impl MintState {
+    // discriminator + token mint + bump + seed version + total processed + total refunded + daily volume cap
+    // + volume window start + volume in window + hot balance target + cold treasury + min token balance
+    // + min withdrawal + account creation fee + migration target + low balance threshold + day open
+    // + day open totals + previous day open + previous day open totals + last finalized day + expected decimals
+    pub const LEN: usize = 8 + 32 + 1 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 33 + 8 + 8 + 8 + 33 + 8 + 4 + 16 + 4 + 16 + 5 + 2;
+
+    pub fn init(&mut self, token_mint: Pubkey, bump: u8) {
+        self.token_mint = token_mint;
+        self.bump = bump;
+        self.program_token_seed_version = 0;
+        self.total_processed = 0;
+        self.total_refunded = 0;
+        self.daily_volume_cap = 0;
+        self.volume_window_start = 0;
+        self.volume_in_window = 0;
+        self.hot_balance_target = 0;
+        self.cold_treasury = None;
+        self.min_token_balance = 0;
+        self.min_withdrawal = 0;
+        self.account_creation_fee = 0;
+        self.migration_target = None;
+        self.low_balance_threshold = 0;
+        self.day_open = 0;
+        self.day_open_processed = 0;
+        self.day_open_refunded = 0;
+        self.previous_day_open = 0;
+        self.previous_day_open_processed = 0;
+        self.previous_day_open_refunded = 0;
+        self.last_finalized_day = None;
+        self.expected_decimals = None;
+    }
+
+    pub fn record_refund(&mut self, amount: u64, now: i64) -> Result<()> {
+        self.open_day(now);
+        self.total_refunded = self.total_refunded
+            .checked_add(amount)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Extra seed of the program token account PDAs. Version 0 adds nothing so the original
+    /// `[b"program_token_account", mint]` addresses stay valid.
+    pub fn program_token_seed(&self) -> &[u8] {
+        if self.program_token_seed_version == 0 {
+            &[]
+        } else {
+            std::slice::from_ref(&self.program_token_seed_version)
+        }
+    }
+
+    /// Adds `amount` to the current 24h volume window, rolling the window once a day has passed,
+    /// and with `enforce_cap` rejects it if it would exceed a non-zero `daily_volume_cap`.
+    pub fn track_daily_volume(&mut self, amount: u64, now: i64, enforce_cap: bool) -> Result<()> {
+        if now >= self.volume_window_start.saturating_add(SECONDS_PER_DAY) {
+            self.volume_window_start = now;
+            self.volume_in_window = 0;
+        }
+
+        let volume_in_window = self.volume_in_window
+            .checked_add(amount)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+        require!(
+            !enforce_cap || self.daily_volume_cap == 0 || volume_in_window <= self.daily_volume_cap,
+            PaymentProcessorError::DailyVolumeCapExceeded
+        );
+
+        self.volume_in_window = volume_in_window;
+        Ok(())
+    }
+
+    /// Snapshots the all-time counters when the first payment or refund of a day comes in, before
+    /// it is recorded, so they can be read back as the totals at the day boundary.
+    pub fn open_day(&mut self, now: i64) {
+        let today = u32::try_from(now / SECONDS_PER_DAY).unwrap_or(u32::MAX);
+        if today <= self.day_open {
+            return;
+        }
+
+        self.previous_day_open = self.day_open;
+        self.previous_day_open_processed = self.day_open_processed;
+        self.previous_day_open_refunded = self.day_open_refunded;
+        self.day_open = today;
+        self.day_open_processed = self.total_processed;
+        self.day_open_refunded = self.total_refunded;
+    }
+
+    /// `(total_processed, total_refunded)` when `day` opened. The counters only change on days with
+    /// activity, so this is known for any day after the previous day with activity, None before.
+    pub fn totals_at_day_start(&self, day: u32) -> Option<(u64, u64)> {
+        if day > self.day_open {
+            Some((self.total_processed, self.total_refunded))
+        } else if day > self.previous_day_open {
+            Some((self.day_open_processed, self.day_open_refunded))
+        } else if day == self.previous_day_open {
+            Some((self.previous_day_open_processed, self.previous_day_open_refunded))
+        } else {
+            None
+        }
+    }
+
+    /// First day, opening and closing `(total_processed, total_refunded)` of the next summary ending
+    /// on `day`. `previous_closing` holds the totals of the last finalized summary, if any. Only the
+    /// first record of a mint, or `fold_skipped_days`, may start anywhere but the day after it.
+    #[allow(clippy::type_complexity)]
+    pub fn day_range_totals(
+        &self,
+        previous_closing: Option<(u64, u64)>,
+        day: u32,
+        fold_skipped_days: bool,
+    ) -> Result<(u32, (u64, u64), (u64, u64))> {
+        let (first_day, opening) = match (self.last_finalized_day, previous_closing) {
+            (Some(last_finalized_day), Some(previous_closing)) => {
+                let first_day = last_finalized_day
+                    .checked_add(1)
+                    .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+                require!(
+                    day == first_day || (fold_skipped_days && day > first_day),
+                    PaymentProcessorError::PreviousDayNotFinalized
+                );
+                (first_day, previous_closing)
+            }
+            (Some(_), None) => return err!(PaymentProcessorError::PreviousDayNotFinalized),
+            (None, _) => (day, self.totals_at_day_start(day).ok_or(PaymentProcessorError::DayBoundaryLost)?),
+        };
+
+        // The totals when the next day opened, not when the summary is recorded
+        let closing = day
+            .checked_add(1)
+            .and_then(|next_day| self.totals_at_day_start(next_day))
+            .ok_or(PaymentProcessorError::DayBoundaryLost)?;
+        Ok((first_day, opening, closing))
+    }
+
+    /// Share of the mint's all-time processed volume set by the program-wide `reserve_bps`
+    pub fn required_reserve(&self, program_state: &ProgramState) -> u64 {
+        apply_bps(self.total_processed, program_state.reserve_bps, program_state.rounding_mode)
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = ProgramState::space(0, 1),
+        seeds = [b"program_state"],
+        bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = MintState::LEN,
+        seeds = [b"mint_state", ACCEPTED_MINT.as_ref()],
+        bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::LibertaiPaymentProcessor>,
+    // Only the upgrade authority can initialize, so nobody can front-run the deployment and claim the program_state PDA
+    #[account(constraint = program_data.upgrade_authority_address == Some(payer.key()) @ PaymentProcessorError::UnauthorizedAccess)]
+    pub program_data: Account<'info, ProgramData>,
+}
+
+#[derive(Accounts)]
+#[instruction(config: InitConfig)]
+pub struct InitializeWithConfig<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = ProgramState::space(config.initial_admins.len(), 1),
+        seeds = [b"program_state"],
+        bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = MintState::LEN,
+        seeds = [b"mint_state", ACCEPTED_MINT.as_ref()],
+        bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::LibertaiPaymentProcessor>,
+    // Only the upgrade authority can initialize, so nobody can front-run the deployment and claim the program_state PDA
+    #[account(constraint = program_data.upgrade_authority_address == Some(payer.key()) @ PaymentProcessorError::UnauthorizedAccess)]
+    pub program_data: Account<'info, ProgramData>,
+}
+
+// program_state as first deployed on mainnet, before any setting was added
+#[derive(AnchorDeserialize)]
+struct LegacyProgramState {
+    owner: Pubkey,
+    admins: Vec<Pubkey>,
+    bump: u8,
+}
+
+impl LegacyProgramState {
+    // discriminator + owner + vec length + admins + bump
+    fn space(admin_count: usize) -> usize {
+        8 + 32 + 4 + admin_count * 32 + 1
+    }
+}
+
+// Parses `data` as a program_state in the layout first deployed and converts it to the current one,
+// if `signer` is its owner
+fn migrated_program_state(data: &[u8], signer: &Pubkey) -> Result<ProgramState> {
+    require!(
+        data.starts_with(ProgramState::DISCRIMINATOR),
+        anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+    );
+    let legacy = LegacyProgramState::deserialize(&mut &data[ProgramState::DISCRIMINATOR.len()..])
+        .map_err(|_| PaymentProcessorError::ProgramStateAlreadyMigrated)?;
+    require!(
+        data.len() == LegacyProgramState::space(legacy.admins.len()),
+        PaymentProcessorError::ProgramStateAlreadyMigrated
+    );
+    require!(legacy.owner == *signer, PaymentProcessorError::OnlyOwner);
+    require!(
+        legacy.admins.len() <= MAX_ADMINS,
+        PaymentProcessorError::TooManyAdmins
+    );
+
+    let mut program_state = ProgramState::default();
+    program_state.init(legacy.owner, legacy.bump);
+    program_state.admins = legacy.admins
+        .into_iter()
+        .map(|key| AdminEntry { key, expires_ts: None, can_withdraw: true, can_manage: true })
+        .collect();
+    Ok(program_state)
+}
+
+#[derive(Accounts)]
+pub struct MigrateProgramState<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump,
+        owner = crate::ID
+    )]
+    /// CHECK: program_state in the layout first deployed, which no longer deserializes as ProgramState - parsed manually
+    pub program_state: UncheckedAccount<'info>,
+
+    // init_if_needed so that running it again fails on the layout check with a clear error
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = MintState::LEN,
+        seeds = [b"mint_state", ACCEPTED_MINT.as_ref()],
+        bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct PaymentReceipt {
+    pub user: Pubkey,
+    pub reference_id: u64,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+impl PaymentReceipt {
+    pub const LEN: usize = 8 + 32 + 8 + 8 + 8; // discriminator + user + reference_id + amount + timestamp
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, reference_id: Option<u64>)]
+pub struct ProcessPayment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    // Only passed along with a reference_id, `init` rejects a reference the user already paid for
+    #[account(
+        init,
+        payer = user,
+        space = PaymentReceipt::LEN,
+        seeds = [b"receipt", user.key().as_ref(), reference_id.unwrap_or_default().to_le_bytes().as_ref()],
+        bump
+    )]
+    pub payment_receipt: Option<Account<'info, PaymentReceipt>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = PublicStats::LEN,
+        seeds = [b"public_stats", token_mint.key().as_ref()],
+        bump
+    )]
+    pub public_stats: Account<'info, PublicStats>,
+
+    #[account(
+        seeds = [b"user_pause", user.key().as_ref()],
+        bump
+    )]
+    /// CHECK: UserPause PDA of the user, which may not exist - deserialized manually when present
+    pub user_pause: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub user_token_account: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually  
+    pub program_token_account: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually, only needed once the hot balance target is exceeded
+    pub cold_treasury: Option<UncheckedAccount<'info>>,
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program - checked against the accepted mints
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[account]
+pub struct DiscountNonce {
+    pub user: Pubkey,
+    pub used_at: i64,
+}
+
+impl DiscountNonce {
+    pub const LEN: usize = 8 + 32 + 8; // discriminator + user + used_at
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, discount_bps: u16, nonce: u64)]
+pub struct ProcessPaymentWithDiscount<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    #[account(
+        init,
+        payer = user,
+        space = DiscountNonce::LEN,
+        seeds = [b"discount_nonce", nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub discount_nonce: Account<'info, DiscountNonce>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = PublicStats::LEN,
+        seeds = [b"public_stats", token_mint.key().as_ref()],
+        bump
+    )]
+    pub public_stats: Account<'info, PublicStats>,
+
+    #[account(
+        seeds = [b"user_pause", user.key().as_ref()],
+        bump
+    )]
+    /// CHECK: UserPause PDA of the user, which may not exist - deserialized manually when present
+    pub user_pause: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub user_token_account: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually, only needed once the hot balance target is exceeded
+    pub cold_treasury: Option<UncheckedAccount<'info>>,
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program - checked against the accepted mints
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: Instructions sysvar, used to inspect the Ed25519 signature verification instruction
+    pub instructions: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Payment totals of one mint, kept at `[b"public_stats", mint]` and meant to be read directly by other
+/// programs. The layout is stable and new fields will only ever be appended: discriminator (8)
+/// | total_processed (u64 LE) | payment_count (u64 LE) | last_update_slot (u64 LE).
+#[account]
+pub struct PublicStats {
+    pub total_processed: u64,
+    pub payment_count: u64,
+    pub last_update_slot: u64,
+}
+
+impl PublicStats {
+    pub const LEN: usize = 8 + 8 + 8 + 8; // discriminator + total_processed + payment_count + last_update_slot
+
+    pub fn record_payment(&mut self, amount: u64, slot: u64) -> Result<()> {
+        self.total_processed = self.total_processed
+            .checked_add(amount)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+        self.payment_count = self.payment_count
+            .checked_add(1)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+        self.last_update_slot = slot;
+        Ok(())
+    }
+}
+
+#[account]
+pub struct UserPause {
+    pub user: Pubkey,
+    pub until_ts: i64,
+}
+
+impl UserPause {
+    pub const LEN: usize = 8 + 32 + 8; // discriminator + user + until_ts
+}
+
+#[account]
+pub struct KycRegistry {
+    pub owner: Pubkey,
+    pub added_at: i64,
+}
+
+impl KycRegistry {
+    pub const LEN: usize = 8 + 32 + 8; // discriminator + owner + added_at
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct AddKyc<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = KycRegistry::LEN,
+        seeds = [b"kyc", owner.as_ref()],
+        bump
+    )]
+    pub kyc_registry: Account<'info, KycRegistry>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct RemoveKyc<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"kyc", owner.as_ref()],
+        bump
+    )]
+    pub kyc_registry: Account<'info, KycRegistry>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct PauseUser<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.can_manage(&authority.key()) @PaymentProcessorError::UnauthorizedAccess
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = UserPause::LEN,
+        seeds = [b"user_pause", user.as_ref()],
+        bump
+    )]
+    pub user_pause: Account<'info, UserPause>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TogglePause<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.can_manage(&authority.key()) @PaymentProcessorError::UnauthorizedAccess
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_mint: Pubkey)]
+pub struct UpdateMintConfig<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint_state", token_mint.as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessPaymentSol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.can_manage(&authority.key()) @PaymentProcessorError::UnauthorizedAccess,
+        realloc = ProgramState::space(program_state.admins.len() + 1, program_state.accepted_mints.len()),
+        realloc::payer = authority,
+        realloc::zero = false,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.can_manage(&authority.key()) @PaymentProcessorError::UnauthorizedAccess,
+        realloc = ProgramState::space(program_state.admins.len().saturating_sub(1), program_state.accepted_mints.len()),
+        realloc::payer = authority,
+        realloc::zero = false,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddAcceptedMint<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner,
+        realloc = ProgramState::space(program_state.admins.len(), program_state.accepted_mints.len() + 1),
+        realloc::payer = authority,
+        realloc::zero = false,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = MintState::LEN,
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program - validated manually
+    pub token_mint: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAcceptedMint<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner,
+        realloc = ProgramState::space(program_state.admins.len(), program_state.accepted_mints.len().saturating_sub(1)),
+        realloc::payer = authority,
+        realloc::zero = false,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PruneExpiredAdmins<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.can_manage(&authority.key()) @PaymentProcessorError::UnauthorizedAccess,
+        realloc = ProgramState::space(program_state.active_admin_count(), program_state.accepted_mints.len()),
+        realloc::payer = authority,
+        realloc::zero = false,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeOwner<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwnerCanChangeOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwner<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.pending_owner == Some(new_owner.key()) @PaymentProcessorError::NotPendingOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    pub new_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetAdmins<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+}
+
+#[derive(Accounts)]
+pub struct ViewState<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_mint: Pubkey)]
+pub struct ViewMintState<'info> {
+    #[account(
+        seeds = [b"mint_state", token_mint.as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+}
+
+#[derive(Accounts)]
+pub struct GetAccountingSnapshot<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    #[account(
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+
+    #[account(
+        constraint = program_state.accepted_mints.contains(&token_mint.key()) @ PaymentProcessorError::InvalidTokenMint
+    )]
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetAcceptedTokenPrograms {}
+
+#[derive(Accounts)]
+pub struct QuoteNet<'info> {
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program - parsed manually
+    pub token_mint: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct IsNonceUsed<'info> {
+    /// CHECK: DiscountNonce or WithdrawNonce PDA, which may not exist - address checked against
+    /// `NonceKind::address` and deserialized manually when present
+    pub nonce_account: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.can_withdraw(&authority.key()) @PaymentProcessorError::UnauthorizedAccess
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_budget", authority.key().as_ref(), token_mint.key().as_ref()],
+        bump
+    )]
+    /// CHECK: AdminBudget PDA of the signer for the mint, which may not exist - deserialized manually when present
+    pub admin_budget: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+    
+    #[account(mut)]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub destination_token_account: AccountInfo<'info>,
+    /// CHECK: KycRegistry PDA of the destination owner, checked manually - only needed when KYC'd destinations are required
+    pub kyc_registry: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
+}
+
+#[account]
+pub struct AdminBudget {
+    pub spent_in_window: u64,
+    pub window_start: i64,
+    pub limit: u64,
+}
+
+impl AdminBudget {
+    pub const LEN: usize = 8 + 8 + 8 + 8; // discriminator + spent_in_window + window_start + limit
+
+    /// Adds `amount` to the current 24h window, rolling the window once a day has passed.
+    pub fn charge(&mut self, amount: u64, now: i64) -> Result<()> {
+        if now >= self.window_start.saturating_add(SECONDS_PER_DAY) {
+            self.window_start = now;
+            self.spent_in_window = 0;
+        }
+
+        let spent_in_window = self.spent_in_window
+            .checked_add(amount)
+            .ok_or(PaymentProcessorError::ArithmeticOverflow)?;
+        require!(
+            spent_in_window <= self.limit,
+            PaymentProcessorError::AdminBudgetExceeded
+        );
+
+        self.spent_in_window = spent_in_window;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(admin: Pubkey, token_mint: Pubkey)]
+pub struct SetAdminBudget<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = AdminBudget::LEN,
+        seeds = [b"admin_budget", admin.as_ref(), token_mint.as_ref()],
+        bump
+    )]
+    pub admin_budget: Account<'info, AdminBudget>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct PeriodCheckpoint {
+    pub token_mint: Pubkey,
+    pub period_id: u64,
+    pub total_processed: u64,
+    pub total_refunded: u64,
+    pub total_sol_withdrawn: u64,
+    pub recorded_at: i64,
+}
+
+impl PeriodCheckpoint {
+    // discriminator + token_mint + period_id + total_processed + total_refunded + total_sol_withdrawn + recorded_at
+    pub const LEN: usize = 8 + 32 + 8 + 8 + 8 + 8 + 8;
+}
+
+#[derive(Accounts)]
+#[instruction(token_mint: Pubkey, period_id: u64)]
+pub struct Checkpoint<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        seeds = [b"mint_state", token_mint.as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = PeriodCheckpoint::LEN,
+        seeds = [b"checkpoint", token_mint.as_ref(), period_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub checkpoint: Account<'info, PeriodCheckpoint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct DaySummary {
+    pub token_mint: Pubkey,
+    pub day: u32,
+    // Earlier than `day` when finalize_days_through folded the skipped days into this summary
+    pub first_day: u32,
+    pub volume: u64,
+    pub refunded: u64,
+    // All-time counters when the next day opened, diffed against by the next day
+    pub total_processed: u64,
+    pub total_refunded: u64,
+    pub finalized_at: i64,
+}
+
+impl DaySummary {
+    // discriminator + token_mint + day + first_day + volume + refunded + total_processed + total_refunded
+    // + finalized_at
+    pub const LEN: usize = 8 + 32 + 4 + 4 + 8 + 8 + 8 + 8 + 8;
+}
+
+#[derive(Accounts)]
+#[instruction(token_mint: Pubkey, day: u32)]
+pub struct FinalizeDay<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint_state", token_mint.as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = DaySummary::LEN,
+        seeds = [b"day", token_mint.as_ref(), day.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub day_summary: Account<'info, DaySummary>,
+
+    #[account(
+        seeds = [b"day", token_mint.as_ref(), mint_state.last_finalized_day.unwrap_or_default().to_le_bytes().as_ref()],
+        bump
+    )]
+    /// CHECK: DaySummary PDA of the last finalized day, only read when the mint has one - deserialized manually
+    pub previous_day_summary: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct WithdrawNonce {
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub used_at: i64,
+}
+
+impl WithdrawNonce {
+    pub const LEN: usize = 8 + 32 + 8 + 8; // discriminator + authority + amount + used_at
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, nonce: u64)]
+pub struct WithdrawIdempotent<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.can_withdraw(&authority.key()) @PaymentProcessorError::UnauthorizedAccess
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = WithdrawNonce::LEN,
+        seeds = [
+            b"withdraw_nonce",
+            authority.key().as_ref(),
+            token_mint.key().as_ref(),
+            nonce.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub withdraw_nonce: Account<'info, WithdrawNonce>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_budget", authority.key().as_ref(), token_mint.key().as_ref()],
+        bump
+    )]
+    /// CHECK: AdminBudget PDA of the signer for the mint, which may not exist - deserialized manually when present
+    pub admin_budget: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+
+    #[account(mut)]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub destination_token_account: AccountInfo<'info>,
+    /// CHECK: KycRegistry PDA of the destination owner, checked manually - only needed when KYC'd destinations are required
+    pub kyc_registry: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefundBatch<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.can_withdraw(&authority.key()) @PaymentProcessorError::UnauthorizedAccess
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_budget", authority.key().as_ref(), token_mint.key().as_ref()],
+        bump
+    )]
+    /// CHECK: AdminBudget PDA of the signer for the mint, which may not exist - deserialized manually when present
+    pub admin_budget: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Refund<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.can_withdraw(&authority.key()) @PaymentProcessorError::UnauthorizedAccess
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_budget", authority.key().as_ref(), token_mint.key().as_ref()],
+        bump
+    )]
+    /// CHECK: AdminBudget PDA of the signer for the mint, which may not exist - deserialized manually when present
+    pub admin_budget: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+
+    #[account(mut)]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub user_token_account: AccountInfo<'info>,
+    /// CHECK: KycRegistry PDA of the user token account owner, checked manually - only needed when KYC'd destinations are required
+    pub kyc_registry: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Clawback<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
 
-    pub fn is_admin(&self, pubkey: &Pubkey) -> bool {
-        self.admins.contains(pubkey)
-    }
-    
-    pub fn is_owner_or_admin(&self, pubkey: &Pubkey) -> bool {
-        self.owner == *pubkey || self.is_admin(pubkey)
-    }
+    #[account(
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub source_token_account: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+pub struct RecoverNoncanonicalAccount<'info> {
     #[account(
-        init,
-        payer = payer,
-        space = ProgramState::INITIAL_LEN,
         seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub source_token_account: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
         bump
     )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+
+    #[account(mut)]
+    /// CHECK: Receives the rent lamports of the closed source account
+    pub receiver: AccountInfo<'info>,
+
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateTreasury<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
     pub program_state: Account<'info, ProgramState>,
-    
+
+    #[account(
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+
     #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
+    /// CHECK: Token account of the new program version, must match the migration target - validated manually
+    pub destination_token_account: AccountInfo<'info>,
+
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
-pub struct ProcessPayment<'info> {
+pub struct CloseProgramTokenAccount<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
+    pub program_token_account: AccountInfo<'info>,
+
     #[account(mut)]
-    pub user: Signer<'info>,
-    
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually, only needed to sweep
+    pub destination_token_account: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    /// CHECK: Receives the reclaimed rent lamports
+    pub receiver: AccountInfo<'info>,
+
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_seed_version: u8)]
+pub struct ReseedProgramTokenAccount<'info> {
+    #[account(
+        seeds = [b"program_state"],
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
     #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
     /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
-    pub user_token_account: AccountInfo<'info>,
-    
+    pub old_program_token_account: AccountInfo<'info>,
+
     #[account(
-        init_if_needed,
-        payer = user,
-        space = 165, // Token account size (both SPL Token and Token 2022)
-        seeds = [b"program_token_account", token_mint.key().as_ref()],
+        init,
+        payer = authority,
+        space = old_program_token_account.data_len(),
+        seeds = [b"program_token_account", token_mint.key().as_ref(), new_seed_version.to_le_bytes().as_ref()],
         bump,
         owner = token_program.key(),
     )]
-    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually  
-    pub program_token_account: AccountInfo<'info>,
+    /// CHECK: Token account can be from either SPL Token or Token 2022 program - initialized manually
+    pub new_program_token_account: AccountInfo<'info>,
+
     #[account(
-        constraint = token_mint.key() == ACCEPTED_MINT @ PaymentProcessorError::InvalidTokenMint
+        constraint = program_state.accepted_mints.contains(&token_mint.key()) @ PaymentProcessorError::InvalidTokenMint
     )]
     /// CHECK: Token mint can be from either SPL Token or Token 2022 program
     pub token_mint: AccountInfo<'info>,
@@ -450,133 +4205,271 @@ pub struct ProcessPayment<'info> {
 }
 
 #[derive(Accounts)]
-pub struct ProcessPaymentSol<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    
+pub struct InitializeProgramTokenAccount<'info> {
     #[account(
-        mut,
         seeds = [b"program_state"],
-        bump = program_state.bump
+        bump = program_state.bump,
+        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwner
     )]
     pub program_state: Account<'info, ProgramState>,
-    
+
+    #[account(
+        seeds = [b"mint_state", token_mint.key().as_ref()],
+        bump = mint_state.bump
+    )]
+    pub mint_state: Account<'info, MintState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"program_token_account", token_mint.key().as_ref(), mint_state.program_token_seed()],
+        bump
+    )]
+    /// CHECK: Created and initialized manually for the mint's token program
+    pub program_token_account: AccountInfo<'info>,
+
+    #[account(
+        constraint = program_state.accepted_mints.contains(&token_mint.key()) @ PaymentProcessorError::InvalidTokenMint
+    )]
+    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
+    pub token_mint: AccountInfo<'info>,
+    /// CHECK: Token program can be either SPL Token or Token 2022
+    pub token_program: AccountInfo<'info>,
     pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct AddAdmin<'info> {
+pub struct WithdrawSol<'info> {
     #[account(
         mut,
         seeds = [b"program_state"],
         bump = program_state.bump,
-        constraint = program_state.is_owner_or_admin(&authority.key()) @PaymentProcessorError::UnauthorizedAccess,
-        realloc = ProgramState::INITIAL_LEN + (program_state.admins.len() + 1) * 32,
-        realloc::payer = authority,
-        realloc::zero = false,
+        constraint = program_state.can_withdraw(&authority.key()) @PaymentProcessorError::UnauthorizedAccess
     )]
     pub program_state: Account<'info, ProgramState>,
     
     #[account(mut)]
     pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
+
+    /// CHECK 
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+}
+
+#[event]
+pub struct PaymentEvent {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+    pub token_mint: Pubkey,
+    // Total processed after this payment, lets indexers detect missed events
+    pub cumulative_total: u64,
+    // Program token balance after this payment, only set when balance events are enabled
+    pub treasury_balance: Option<u64>,
+    // Free-form invoice reference from the payer, empty when none was given
+    pub memo: String,
+    // Charged on top of `amount` by the payment that created the program token account
+    pub account_creation_fee: u64,
+    // What the payment is worth to the user. Above `amount` for discounted payments, which are
+    // credited in full while only the tokens received count towards `cumulative_total`.
+    pub credited_amount: u64,
+}
+
+#[event]
+pub struct DiscountAppliedEvent {
+    pub user: Pubkey,
+    pub nonce: u64,
+    pub discount_bps: u16,
+    pub amount: u64,
+    pub charged_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserPausedEvent {
+    pub user: Pubkey,
+    pub until_ts: i64,
+    pub paused_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ColdTransferEvent {
+    pub token_mint: Pubkey,
+    pub cold_treasury: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RecoveryEvent {
+    pub token_mint: Pubkey,
+    pub source: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminAddedEvent {
+    pub admin: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminRemovedEvent {
+    pub admin: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OwnerChangedEvent {
+    pub old_owner: Pubkey,
+    pub new_owner: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminExpiredEvent {
+    pub admin: Pubkey,
+    pub expired_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InitSubsidyUsedEvent {
+    pub token_mint: Pubkey,
+    pub program_token_account: Pubkey,
+    pub lamports: u64,
+    pub remaining_subsidy: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ClawbackEvent {
+    pub source: Pubkey,
+    pub source_owner: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseToggled {
+    pub paused: bool,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AcceptedMintAddedEvent {
+    pub token_mint: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AcceptedMintRemovedEvent {
+    pub token_mint: Pubkey,
+    pub by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseMaskSetEvent {
+    pub previous_mask: u32,
+    pub pause_mask: u32,
+    pub set_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CheckpointEvent {
+    pub token_mint: Pubkey,
+    pub period_id: u64,
+    pub total_processed: u64,
+    pub total_refunded: u64,
+    pub total_sol_withdrawn: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DayFinalizedEvent {
+    pub token_mint: Pubkey,
+    pub day: u32,
+    pub volume: u64,
+    pub refunded: u64,
+    pub timestamp: i64,
+    pub first_day: u32,
 }
 
-#[derive(Accounts)]
-pub struct RemoveAdmin<'info> {
-    #[account(
-        mut,
-        seeds = [b"program_state"],
-        bump = program_state.bump,
-        constraint = program_state.is_owner_or_admin(&authority.key()) @PaymentProcessorError::UnauthorizedAccess,
-        realloc = ProgramState::INITIAL_LEN + (program_state.admins.len().saturating_sub(1)) * 32,
-        realloc::payer = authority,
-        realloc::zero = false,
-    )]
-    pub program_state: Account<'info, ProgramState>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct RefundEvent {
+    pub to: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct ChangeOwner<'info> {
-    #[account(
-        mut,
-        seeds = [b"program_state"],
-        bump = program_state.bump,
-        constraint = program_state.owner == authority.key() @PaymentProcessorError::OnlyOwnerCanChangeOwner
-    )]
-    pub program_state: Account<'info, ProgramState>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
+#[event]
+pub struct SweepEvent {
+    pub token_mint: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct GetAdmins<'info> {
-    #[account(
-        seeds = [b"program_state"],
-        bump = program_state.bump
-    )]
-    pub program_state: Account<'info, ProgramState>,
+#[event]
+pub struct TreasuryMigratedEvent {
+    pub token_mint: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(
-        seeds = [b"program_state"],
-        bump = program_state.bump,
-        constraint = program_state.is_owner_or_admin(&authority.key()) @PaymentProcessorError::UnauthorizedAccess
-    )]
-    pub program_state: Account<'info, ProgramState>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    #[account(
-        mut,
-        seeds = [b"program_token_account", token_mint.key().as_ref()],
-        bump
-    )]
-    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
-    pub program_token_account: AccountInfo<'info>,
-    
-    #[account(mut)]
-    /// CHECK: Token account can be from either SPL Token or Token 2022 program - validated manually
-    pub destination_token_account: AccountInfo<'info>,
-    
-    /// CHECK: Token mint can be from either SPL Token or Token 2022 program
-    pub token_mint: AccountInfo<'info>,
-    /// CHECK: Token program can be either SPL Token or Token 2022
-    pub token_program: AccountInfo<'info>,
+#[event]
+pub struct TokenAccountClosedEvent {
+    pub token_mint: Pubkey,
+    pub receiver: Pubkey,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct WithdrawSol<'info> {
-    #[account(
-        mut,
-        seeds = [b"program_state"],
-        bump = program_state.bump,
-        constraint = program_state.is_owner_or_admin(&authority.key()) @PaymentProcessorError::UnauthorizedAccess
-    )]
-    pub program_state: Account<'info, ProgramState>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
+#[event]
+pub struct ProgramTokenAccountReseededEvent {
+    pub token_mint: Pubkey,
+    pub old_account: Pubkey,
+    pub new_account: Pubkey,
+    pub seed_version: u8,
+    pub amount: u64,
+}
 
-    /// CHECK 
-    #[account(mut)]
-    pub destination: AccountInfo<'info>,
+#[event]
+pub struct ProgramTokenAccountInitializedEvent {
+    pub token_mint: Pubkey,
+    pub program_token_account: Pubkey,
+    pub token_program: Pubkey,
+    pub space: u64,
 }
 
 #[event]
-pub struct PaymentEvent {
-    pub user: Pubkey,
+pub struct WithdrawalEvent {
+    pub authority: Pubkey,
+    pub destination: Pubkey,
     pub amount: u64,
+    // None for SOL withdrawals
+    pub token_mint: Option<Pubkey>,
     pub timestamp: i64,
+    // Program token balance after this withdrawal, only set when balance events are enabled
+    pub treasury_balance: Option<u64>,
+}
+
+#[event]
+pub struct LowBalanceEvent {
     pub token_mint: Pubkey,
+    pub balance: u64,
+    pub threshold: u64,
 }
 
 #[event]
@@ -603,7 +4496,7 @@ pub enum PaymentProcessorError {
     #[msg("Insufficient funds in program token account")]
     InsufficientFunds,
     
-    #[msg("Invalid token mint - only mntpN8z1d29f3MWhMD7VqZFpeYmbD88MgwS3Bkz8y7u is accepted")]
+    #[msg("Invalid token mint - the mint is not accepted")]
     InvalidTokenMint,
     
     #[msg("Invalid token program - only SPL Token and Token 2022 programs are accepted")]
@@ -611,6 +4504,132 @@ pub enum PaymentProcessorError {
     
     #[msg("Invalid token account - account data is malformed or constraints not met")]
     InvalidTokenAccount,
+
+    #[msg("Malformed token account - data length does not match the token program's account layout")]
+    MalformedTokenAccount,
+
+    #[msg("Only the owner can perform this action")]
+    OnlyOwner,
+
+    #[msg("Invalid discount - must be at most 10000 basis points")]
+    InvalidDiscount,
+
+    #[msg("Discounts are disabled - no discount signer configured")]
+    DiscountsDisabled,
+
+    #[msg("Missing or invalid Ed25519 discount signature")]
+    InvalidDiscountSignature,
+
+    #[msg("Invalid basis points - must be at most 10000")]
+    InvalidBasisPoints,
+
+    #[msg("Withdrawal would drop the program token balance below the required reserve")]
+    BelowReserve,
+
+    #[msg("Arithmetic overflow")]
+    ArithmeticOverflow,
+
+    #[msg("Program token account still holds tokens - sweep them before closing")]
+    TokenAccountNotEmpty,
+
+    #[msg("A destination token account is required to sweep the remaining balance")]
+    MissingSweepDestination,
+
+    #[msg("New seed version must be greater than the current one")]
+    InvalidSeedVersion,
+
+    #[msg("Payment would exceed the daily volume cap")]
+    DailyVolumeCapExceeded,
+
+    #[msg("Program token account is already initialized")]
+    ProgramTokenAccountAlreadyInitialized,
+
+    #[msg("Invalid batch - must be non-empty, within the size limit and match the accounts passed")]
+    InvalidBatch,
+
+    #[msg("Invalid configuration")]
+    InvalidConfig,
+
+    #[msg("Token mint has a disallowed extension")]
+    DisallowedMintExtension,
+
+    #[msg("User is paused")]
+    UserPaused,
+
+    #[msg("Program state would exceed its maximum size")]
+    ProgramStateTooLarge,
+
+    #[msg("Cold treasury account is missing or does not match the configured one")]
+    InvalidColdTreasury,
+
+    #[msg("Admin expiry must be in the future")]
+    InvalidAdminExpiry,
+
+    #[msg("Invalid rounding mode")]
+    InvalidRoundingMode,
+
+    #[msg("Withdrawal would drop the program token balance below the configured minimum")]
+    BelowMinBalance,
+
+    #[msg("Nonce has already been used")]
+    NonceReused,
+
+    #[msg("Withdrawal exceeds the admin's budget for the current window")]
+    AdminBudgetExceeded,
+
+    #[msg("Withdrawal amount is below the configured minimum")]
+    WithdrawalTooSmall,
+
+    #[msg("This instruction category is paused")]
+    ProgramPaused,
+
+    #[msg("A checkpoint already exists for this period")]
+    CheckpointExists,
+
+    #[msg("Signer is not the pending owner")]
+    NotPendingOwner,
+
+    #[msg("Too many accepted mints")]
+    TooManyMints,
+
+    #[msg("Mint is already accepted")]
+    MintAlreadyAccepted,
+
+    #[msg("A payment receipt must be passed exactly when a reference id is given")]
+    InvalidPaymentReceipt,
+
+    #[msg("Day has not ended yet")]
+    InvalidDay,
+
+    #[msg("Day has already been finalized")]
+    DayAlreadyFinalized,
+
+    #[msg("Destination token account owner is not KYC'd")]
+    DestinationNotKyc,
+
+    #[msg("Memo is longer than 64 bytes")]
+    MemoTooLong,
+
+    #[msg("Payment transfer did not credit any tokens to the program")]
+    NothingReceived,
+
+    #[msg("Destination is not the approved migration target")]
+    InvalidMigrationTarget,
+
+    #[msg("Too many admins")]
+    TooManyAdmins,
+
+    #[msg("The previous day must be finalized first")]
+    PreviousDayNotFinalized,
+
+    #[msg("The totals at the day boundary are no longer available")]
+    DayBoundaryLost,
+
+    #[msg("Program state is already in the current layout")]
+    ProgramStateAlreadyMigrated,
+
+    #[msg("An admin can only grant the permissions and expiry it holds itself")]
+    PermissionNotHeld,
 }
 
 #[cfg(not(feature = "no-entrypoint"))]
@@ -628,3 +4647,462 @@ security_txt! {
     source_code: "https://github.com/libertai"
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_info<'a>(key: &'a Pubkey, lamports: &'a mut u64, data: &'a mut [u8], owner: &'a Pubkey) -> AccountInfo<'a> {
+        AccountInfo::new(key, false, false, lamports, data, owner, false, 0)
+    }
+
+    fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+        data[0..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data
+    }
+
+    // Token-2022 mint padded to the account type offset, followed by `(type, value)` extension TLVs
+    fn mint_data_with_extensions(extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+        data.push(TOKEN_2022_ACCOUNT_TYPE_MINT);
+        for (extension_type, value) in extensions {
+            data.extend_from_slice(&extension_type.to_le_bytes());
+            data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+            data.extend_from_slice(value);
+        }
+        data
+    }
+
+    // TransferFeeConfig value with `(epoch, maximum_fee, basis_points)` for the older and newer fee
+    fn transfer_fee_config(older: (u64, u64, u16), newer: (u64, u64, u16)) -> Vec<u8> {
+        let mut value = vec![0u8; OLDER_TRANSFER_FEE_OFFSET];
+        for (epoch, maximum_fee, basis_points) in [older, newer] {
+            value.extend_from_slice(&epoch.to_le_bytes());
+            value.extend_from_slice(&maximum_fee.to_le_bytes());
+            value.extend_from_slice(&basis_points.to_le_bytes());
+        }
+        value
+    }
+
+    #[test]
+    fn apply_bps_rounds_according_to_the_mode() {
+        // 15 * 50% = 7.5
+        assert_eq!(apply_bps(15, 5_000, ROUNDING_FLOOR), 7);
+        assert_eq!(apply_bps(15, 5_000, ROUNDING_HALF_UP), 8);
+        assert_eq!(apply_bps(15, 5_000, ROUNDING_CEIL), 8);
+
+        // 1 * 0.01% = 0.0001
+        assert_eq!(apply_bps(1, 1, ROUNDING_FLOOR), 0);
+        assert_eq!(apply_bps(1, 1, ROUNDING_HALF_UP), 0);
+        assert_eq!(apply_bps(1, 1, ROUNDING_CEIL), 1);
+
+        // Exact results are the same in every mode
+        for rounding_mode in [ROUNDING_FLOOR, ROUNDING_HALF_UP, ROUNDING_CEIL] {
+            assert_eq!(apply_bps(14, 5_000, rounding_mode), 7);
+        }
+    }
+
+    #[test]
+    fn apply_bps_falls_back_to_floor_and_does_not_overflow() {
+        assert_eq!(apply_bps(15, 5_000, 42), 7);
+        assert_eq!(apply_bps(u64::MAX, MAX_BPS, ROUNDING_CEIL), u64::MAX);
+        assert_eq!(apply_bps(u64::MAX, 0, ROUNDING_CEIL), 0);
+    }
+
+    #[test]
+    fn track_daily_volume_enforces_the_cap_within_a_day() {
+        let mut mint_state = MintState { daily_volume_cap: 100, ..Default::default() };
+        let start = 10 * SECONDS_PER_DAY;
+
+        mint_state.track_daily_volume(60, start, true).unwrap();
+        assert_eq!(
+            mint_state.track_daily_volume(50, start + 1, true).unwrap_err(),
+            PaymentProcessorError::DailyVolumeCapExceeded.into()
+        );
+        assert_eq!(mint_state.volume_in_window, 60);
+
+        // Exempt signers are tracked but not capped
+        mint_state.track_daily_volume(50, start + 1, false).unwrap();
+        assert_eq!(mint_state.volume_in_window, 110);
+
+        // The window rolls once a day has passed since it started
+        mint_state.track_daily_volume(100, start + SECONDS_PER_DAY, true).unwrap();
+        assert_eq!(mint_state.volume_window_start, start + SECONDS_PER_DAY);
+        assert_eq!(mint_state.volume_in_window, 100);
+    }
+
+    #[test]
+    fn track_daily_volume_without_a_cap_accepts_any_amount() {
+        let mut mint_state = MintState::default();
+        mint_state.track_daily_volume(u64::MAX, SECONDS_PER_DAY, true).unwrap();
+        assert_eq!(
+            mint_state.track_daily_volume(1, SECONDS_PER_DAY, true).unwrap_err(),
+            PaymentProcessorError::ArithmeticOverflow.into()
+        );
+    }
+
+    #[test]
+    fn totals_at_day_start_follow_the_day_boundaries() {
+        let mut mint_state = MintState::default();
+        record_volume(&mut mint_state, 50, 10 * SECONDS_PER_DAY).unwrap();
+        record_volume(&mut mint_state, 25, 10 * SECONDS_PER_DAY + 1).unwrap();
+        mint_state.record_refund(5, 12 * SECONDS_PER_DAY).unwrap();
+        record_volume(&mut mint_state, 100, 15 * SECONDS_PER_DAY).unwrap();
+
+        assert_eq!(mint_state.totals_at_day_start(16), Some((175, 5)));
+        assert_eq!(mint_state.totals_at_day_start(15), Some((75, 5)));
+        assert_eq!(mint_state.totals_at_day_start(13), Some((75, 5)));
+        assert_eq!(mint_state.totals_at_day_start(12), Some((75, 0)));
+        // Boundaries before the previous day with activity are no longer known
+        assert_eq!(mint_state.totals_at_day_start(11), None);
+        assert_eq!(mint_state.totals_at_day_start(10), None);
+    }
+
+    #[test]
+    fn day_range_totals_finalizes_days_in_order() {
+        let mut mint_state = MintState::default();
+        record_volume(&mut mint_state, 50, 10 * SECONDS_PER_DAY).unwrap();
+        record_volume(&mut mint_state, 30, 11 * SECONDS_PER_DAY).unwrap();
+
+        // The first record may start on any day with known boundaries
+        assert_eq!(mint_state.day_range_totals(None, 10, false).unwrap(), (10, (0, 0), (50, 0)));
+
+        mint_state.last_finalized_day = Some(10);
+        assert_eq!(
+            mint_state.day_range_totals(Some((50, 0)), 12, false).unwrap_err(),
+            PaymentProcessorError::PreviousDayNotFinalized.into()
+        );
+        assert_eq!(mint_state.day_range_totals(Some((50, 0)), 11, false).unwrap(), (11, (50, 0), (80, 0)));
+    }
+
+    #[test]
+    fn day_range_totals_folds_days_whose_boundaries_were_lost() {
+        let mut mint_state = MintState::default();
+        record_volume(&mut mint_state, 50, 10 * SECONDS_PER_DAY).unwrap();
+        mint_state.last_finalized_day = Some(10);
+
+        // Activity on three more days while day 11 is still unfinalized
+        record_volume(&mut mint_state, 10, 12 * SECONDS_PER_DAY).unwrap();
+        record_volume(&mut mint_state, 20, 14 * SECONDS_PER_DAY).unwrap();
+        mint_state.record_refund(5, 16 * SECONDS_PER_DAY).unwrap();
+
+        assert_eq!(
+            mint_state.day_range_totals(Some((50, 0)), 11, false).unwrap_err(),
+            PaymentProcessorError::DayBoundaryLost.into()
+        );
+        assert_eq!(
+            mint_state.day_range_totals(Some((50, 0)), 12, true).unwrap_err(),
+            PaymentProcessorError::DayBoundaryLost.into()
+        );
+        // Days 11 to 13, or further, are recorded together up to a boundary still known
+        assert_eq!(mint_state.day_range_totals(Some((50, 0)), 13, true).unwrap(), (11, (50, 0), (60, 0)));
+        assert_eq!(mint_state.day_range_totals(Some((50, 0)), 15, true).unwrap(), (11, (50, 0), (80, 0)));
+        assert_eq!(mint_state.day_range_totals(Some((50, 0)), 16, true).unwrap(), (11, (50, 0), (80, 5)));
+    }
+
+    #[test]
+    fn admin_budget_charge_rolls_its_window_daily() {
+        let mut budget = AdminBudget { spent_in_window: 0, window_start: 0, limit: 100 };
+        let start = 10 * SECONDS_PER_DAY;
+
+        budget.charge(70, start).unwrap();
+        assert_eq!(
+            budget.charge(40, start + 1).unwrap_err(),
+            PaymentProcessorError::AdminBudgetExceeded.into()
+        );
+        assert_eq!(budget.spent_in_window, 70);
+        budget.charge(30, start + 1).unwrap();
+
+        budget.charge(40, start + SECONDS_PER_DAY).unwrap();
+        assert_eq!(budget.spent_in_window, 40);
+    }
+
+    #[test]
+    fn check_mint_extensions_rejects_blocked_extensions_only() {
+        let key = Pubkey::new_unique();
+        let blocked = 1u64 << 9;
+
+        let mut lamports = 0;
+        let mut data = mint_data_with_extensions(&[(TOKEN_2022_EXTENSION_TRANSFER_FEE_CONFIG, vec![0; 4]), (9, vec![])]);
+        let mint = account_info(&key, &mut lamports, &mut data, &TOKEN_2022_PROGRAM_ID);
+        assert_eq!(
+            check_mint_extensions(&mint, blocked).unwrap_err(),
+            PaymentProcessorError::DisallowedMintExtension.into()
+        );
+        check_mint_extensions(&mint, 1u64 << 10).unwrap();
+        check_mint_extensions(&mint, 0).unwrap();
+
+        // SPL Token mints and Token-2022 mints without extensions always pass
+        let mut lamports = 0;
+        let mut data = mint_data_with_extensions(&[(9, vec![])]);
+        let spl_mint = account_info(&key, &mut lamports, &mut data, &SPL_TOKEN_PROGRAM_ID);
+        check_mint_extensions(&spl_mint, blocked).unwrap();
+
+        let mut lamports = 0;
+        let mut data = vec![0u8; MINT_LEN];
+        let plain_mint = account_info(&key, &mut lamports, &mut data, &TOKEN_2022_PROGRAM_ID);
+        check_mint_extensions(&plain_mint, blocked).unwrap();
+    }
+
+    #[test]
+    fn check_mint_extensions_rejects_token_accounts() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = mint_data_with_extensions(&[(9, vec![])]);
+        data[TOKEN_2022_ACCOUNT_TYPE_OFFSET] = TOKEN_2022_ACCOUNT_TYPE_ACCOUNT;
+        let account = account_info(&key, &mut lamports, &mut data, &TOKEN_2022_PROGRAM_ID);
+        assert_eq!(
+            check_mint_extensions(&account, 1).unwrap_err(),
+            PaymentProcessorError::InvalidTokenMint.into()
+        );
+    }
+
+    #[test]
+    fn estimate_transfer_fee_uses_the_fee_of_the_epoch() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = mint_data_with_extensions(&[
+            (9, vec![]),
+            (TOKEN_2022_EXTENSION_TRANSFER_FEE_CONFIG, transfer_fee_config((0, 1_000, 100), (10, 5, 50))),
+        ]);
+        let mint = account_info(&key, &mut lamports, &mut data, &TOKEN_2022_PROGRAM_ID);
+
+        // 1% of 1001 rounded up, below the maximum fee
+        assert_eq!(estimate_transfer_fee(&mint, 1_001, 9).unwrap(), 11);
+        // 0.5% of 1001 rounded up is 6, capped at 5 from the newer fee's epoch
+        assert_eq!(estimate_transfer_fee(&mint, 1_001, 10).unwrap(), 5);
+    }
+
+    #[test]
+    fn estimate_transfer_fee_is_zero_without_a_fee_config() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = mint_data_with_extensions(&[(9, vec![])]);
+        let mint = account_info(&key, &mut lamports, &mut data, &TOKEN_2022_PROGRAM_ID);
+        assert_eq!(estimate_transfer_fee(&mint, 1_000, 0).unwrap(), 0);
+
+        let mut lamports = 0;
+        let mut data = vec![0u8; MINT_LEN];
+        let spl_mint = account_info(&key, &mut lamports, &mut data, &SPL_TOKEN_PROGRAM_ID);
+        assert_eq!(estimate_transfer_fee(&spl_mint, 1_000, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn estimate_transfer_fee_rejects_a_truncated_fee_config() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = mint_data_with_extensions(&[(TOKEN_2022_EXTENSION_TRANSFER_FEE_CONFIG, vec![0; 16])]);
+        let mint = account_info(&key, &mut lamports, &mut data, &TOKEN_2022_PROGRAM_ID);
+        assert_eq!(
+            estimate_transfer_fee(&mint, 1_000, 0).unwrap_err(),
+            PaymentProcessorError::InvalidTokenMint.into()
+        );
+    }
+
+    #[test]
+    fn read_token_account_parses_both_layouts() {
+        let key = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+
+        let mut lamports = 0;
+        let mut data = token_account_data(&mint, &owner, 42);
+        data[72..76].copy_from_slice(&[1, 0, 0, 0]);
+        data[76..108].copy_from_slice(delegate.as_ref());
+        data[121..129].copy_from_slice(&7u64.to_le_bytes());
+        let account = account_info(&key, &mut lamports, &mut data, &SPL_TOKEN_PROGRAM_ID);
+        let token_account = read_token_account(&account).unwrap();
+        assert_eq!(token_account.mint, mint);
+        assert_eq!(token_account.owner, owner);
+        assert_eq!(token_account.amount, 42);
+        assert_eq!(token_account.delegate, Some(delegate));
+        assert_eq!(token_account.delegated_amount, 7);
+
+        // Token-2022 account with extensions
+        let mut lamports = 0;
+        let mut data = token_account_data(&mint, &owner, 42);
+        data.extend_from_slice(&[TOKEN_2022_ACCOUNT_TYPE_ACCOUNT, 0, 0, 0, 0]);
+        let account = account_info(&key, &mut lamports, &mut data, &TOKEN_2022_PROGRAM_ID);
+        let token_account = read_token_account(&account).unwrap();
+        assert_eq!(token_account.amount, 42);
+        assert_eq!(token_account.delegate, None);
+    }
+
+    #[test]
+    fn read_token_account_rejects_other_layouts() {
+        let key = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+
+        // SPL Token accounts never carry extensions
+        let mut lamports = 0;
+        let mut data = token_account_data(&mint, &mint, 42);
+        data.push(TOKEN_2022_ACCOUNT_TYPE_ACCOUNT);
+        let account = account_info(&key, &mut lamports, &mut data, &SPL_TOKEN_PROGRAM_ID);
+        assert_eq!(
+            read_token_account(&account).unwrap_err(),
+            PaymentProcessorError::MalformedTokenAccount.into()
+        );
+
+        // A Token-2022 mint padded to the token account length
+        let mut lamports = 0;
+        let mut data = mint_data_with_extensions(&[(9, vec![])]);
+        let account = account_info(&key, &mut lamports, &mut data, &TOKEN_2022_PROGRAM_ID);
+        assert_eq!(
+            read_token_account(&account).unwrap_err(),
+            PaymentProcessorError::MalformedTokenAccount.into()
+        );
+
+        let mut lamports = 0;
+        let mut data = vec![0u8; MINT_LEN];
+        let account = account_info(&key, &mut lamports, &mut data, &SPL_TOKEN_PROGRAM_ID);
+        assert_eq!(
+            read_token_account(&account).unwrap_err(),
+            PaymentProcessorError::MalformedTokenAccount.into()
+        );
+    }
+
+    #[test]
+    fn can_grant_limits_admins_to_their_own_permissions_and_expiry() {
+        let owner = Pubkey::new_unique();
+        let manager = Pubkey::new_unique();
+        let expiring_manager = Pubkey::new_unique();
+        let program_state = ProgramState {
+            owner,
+            admins: vec![
+                AdminEntry { key: manager, expires_ts: None, can_withdraw: false, can_manage: true },
+                AdminEntry { key: expiring_manager, expires_ts: Some(100), can_withdraw: true, can_manage: true },
+            ],
+            ..Default::default()
+        };
+        let entry = |expires_ts, can_withdraw, can_manage| AdminEntry {
+            key: Pubkey::new_unique(),
+            expires_ts,
+            can_withdraw,
+            can_manage,
+        };
+
+        assert!(program_state.can_grant(&owner, &entry(None, true, true), 0));
+
+        assert!(program_state.can_grant(&manager, &entry(None, false, true), 0));
+        assert!(!program_state.can_grant(&manager, &entry(None, true, false), 0));
+
+        assert!(program_state.can_grant(&expiring_manager, &entry(Some(100), true, true), 0));
+        assert!(!program_state.can_grant(&expiring_manager, &entry(Some(101), true, true), 0));
+        assert!(!program_state.can_grant(&expiring_manager, &entry(None, false, false), 0));
+        // Expired admins grant nothing
+        assert!(!program_state.can_grant(&expiring_manager, &entry(Some(100), false, false), 100));
+    }
+
+    #[test]
+    fn discount_message_binds_the_program_and_mint() {
+        let user = Pubkey::new_unique();
+        let token_mint = Pubkey::new_unique();
+        let message = discount_message(&user, &token_mint, 1_000, 250, 7);
+
+        assert_eq!(message.len(), DISCOUNT_MESSAGE_LEN);
+        assert_eq!(&message[..32], crate::ID.as_ref());
+        assert_eq!(&message[32..64], user.as_ref());
+        assert_eq!(&message[64..96], token_mint.as_ref());
+        assert_eq!(&message[96..104], &1_000u64.to_le_bytes());
+        assert_eq!(&message[104..106], &250u16.to_le_bytes());
+        assert_eq!(&message[106..], &7u64.to_le_bytes());
+        assert_ne!(message, discount_message(&user, &Pubkey::new_unique(), 1_000, 250, 7));
+    }
+
+    #[test]
+    fn checked_space_rejects_growing_past_the_maximum_size() {
+        assert_eq!(
+            ProgramState::checked_space(MAX_ADMINS, MAX_ACCEPTED_MINTS).unwrap(),
+            ProgramState::space(MAX_ADMINS, MAX_ACCEPTED_MINTS)
+        );
+
+        let too_many_admins = (MAX_PROGRAM_STATE_SIZE - ProgramState::INITIAL_LEN) / AdminEntry::LEN + 1;
+        assert_eq!(
+            ProgramState::checked_space(too_many_admins, 0).unwrap_err(),
+            PaymentProcessorError::ProgramStateTooLarge.into()
+        );
+    }
+
+    // program_state in the layout first deployed on mainnet
+    fn legacy_program_state_data(owner: &Pubkey, admins: &[Pubkey], bump: u8) -> Vec<u8> {
+        let mut data = ProgramState::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(owner.as_ref());
+        data.extend_from_slice(&(admins.len() as u32).to_le_bytes());
+        for admin in admins {
+            data.extend_from_slice(admin.as_ref());
+        }
+        data.push(bump);
+        data
+    }
+
+    #[test]
+    fn migrated_program_state_converts_the_legacy_layout() {
+        let owner = Pubkey::new_unique();
+        let admins = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let data = legacy_program_state_data(&owner, &admins, 254);
+        assert_eq!(legacy_program_state_data(&owner, &[], 254).len(), 45);
+
+        let program_state = migrated_program_state(&data, &owner).unwrap();
+        assert_eq!(program_state.owner, owner);
+        assert_eq!(program_state.bump, 254);
+        assert_eq!(program_state.accepted_mints, vec![ACCEPTED_MINT]);
+        assert_eq!(program_state.admins.len(), 2);
+        for (entry, admin) in program_state.admins.iter().zip(admins) {
+            assert_eq!(entry.key, admin);
+            assert!(entry.can_withdraw && entry.can_manage && entry.expires_ts.is_none());
+        }
+    }
+
+    #[test]
+    fn migrated_program_state_rejects_other_signers_and_layouts() {
+        let owner = Pubkey::new_unique();
+        let data = legacy_program_state_data(&owner, &[], 254);
+
+        assert_eq!(
+            migrated_program_state(&data, &Pubkey::new_unique()).unwrap_err(),
+            PaymentProcessorError::OnlyOwner.into()
+        );
+
+        let mut forged = data.clone();
+        forged[0] ^= 1;
+        assert_eq!(
+            migrated_program_state(&forged, &owner).unwrap_err(),
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch.into()
+        );
+
+        let mut padded = data;
+        padded.push(0);
+        assert_eq!(
+            migrated_program_state(&padded, &owner).unwrap_err(),
+            PaymentProcessorError::ProgramStateAlreadyMigrated.into()
+        );
+
+        let mut current = Vec::new();
+        let mut program_state = ProgramState::default();
+        program_state.init(owner, 254);
+        program_state.try_serialize(&mut current).unwrap();
+        assert_eq!(
+            migrated_program_state(&current, &owner).unwrap_err(),
+            PaymentProcessorError::ProgramStateAlreadyMigrated.into()
+        );
+    }
+
+    #[test]
+    fn validate_memo_bounds_the_length_in_bytes() {
+        validate_memo("").unwrap();
+        validate_memo(&"a".repeat(MAX_MEMO_LEN)).unwrap();
+        assert_eq!(
+            validate_memo(&"a".repeat(MAX_MEMO_LEN + 1)).unwrap_err(),
+            PaymentProcessorError::MemoTooLong.into()
+        );
+        // 33 two-byte characters
+        assert_eq!(
+            validate_memo(&"é".repeat(MAX_MEMO_LEN / 2 + 1)).unwrap_err(),
+            PaymentProcessorError::MemoTooLong.into()
+        );
+    }
+}
@@ -0,0 +1,63 @@
+import { BN } from "@coral-xyz/anchor";
+import {
+  createInitializeMintInstruction,
+  createInitializePermanentDelegateInstruction,
+  ExtensionType,
+  getMintLen,
+  TOKEN_2022_PROGRAM_ID,
+} from "@solana/spl-token";
+import { Keypair, PublicKey, sendAndConfirmTransaction, SystemProgram, Transaction } from "@solana/web3.js";
+import { expect } from "chai";
+import { ensureInitialized, expectError, owner, program, programStatePda, provider } from "./setup";
+
+const PERMANENT_DELEGATE_EXTENSION = 12;
+
+// Token-2022 mint whose permanent delegate can move tokens out of any holder's account
+const createPermanentDelegateMint = async (): Promise<PublicKey> => {
+  const mint = Keypair.generate();
+  const space = getMintLen([ExtensionType.PermanentDelegate]);
+  const lamports = await provider.connection.getMinimumBalanceForRentExemption(space);
+  const transaction = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: owner.publicKey,
+      newAccountPubkey: mint.publicKey,
+      space,
+      lamports,
+      programId: TOKEN_2022_PROGRAM_ID,
+    }),
+    createInitializePermanentDelegateInstruction(mint.publicKey, owner.publicKey, TOKEN_2022_PROGRAM_ID),
+    createInitializeMintInstruction(mint.publicKey, 6, owner.publicKey, null, TOKEN_2022_PROGRAM_ID),
+  );
+  await sendAndConfirmTransaction(provider.connection, transaction, [owner, mint]);
+  return mint.publicKey;
+};
+
+describe("accepted mints", () => {
+  const addAcceptedMint = (tokenMint: PublicKey) =>
+    program.methods.addAcceptedMint().accounts({ authority: owner.publicKey, tokenMint }).rpc();
+
+  before(async () => {
+    await ensureInitialized();
+  });
+
+  it("rejects a mint with a blocked extension", async () => {
+    const mint = await createPermanentDelegateMint();
+    const { blockedExtensions } = await program.account.programState.fetch(programStatePda);
+    await program.methods
+      .setBlockedExtensions(new BN(1 << PERMANENT_DELEGATE_EXTENSION))
+      .accounts({ authority: owner.publicKey })
+      .rpc();
+
+    try {
+      await expectError(addAcceptedMint(mint), "DisallowedMintExtension");
+      const { acceptedMints } = await program.account.programState.fetch(programStatePda);
+      expect(acceptedMints.some((accepted) => accepted.equals(mint))).to.equal(false);
+    } finally {
+      await program.methods.setBlockedExtensions(blockedExtensions).accounts({ authority: owner.publicKey }).rpc();
+    }
+  });
+
+  it("rejects an account that is not a mint", async () => {
+    await expectError(addAcceptedMint(Keypair.generate().publicKey), "InvalidTokenMint");
+  });
+});
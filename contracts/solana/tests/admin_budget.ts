@@ -0,0 +1,69 @@
+import { BN } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  airdrop,
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  owner,
+  pay,
+  program,
+  programTokenAccount,
+  tokenBalance,
+} from "./setup";
+
+describe("admin withdrawal budgets", () => {
+  let mint: PublicKey;
+  let destination: PublicKey;
+  const limited = Keypair.generate();
+  const roomy = Keypair.generate();
+
+  const withdraw = (authority: Keypair, amount: number) =>
+    program.methods
+      .withdraw(new BN(amount))
+      .accountsPartial({
+        authority: authority.publicKey,
+        programTokenAccount: programTokenAccount(mint),
+        destinationTokenAccount: destination,
+        kycRegistry: null,
+        tokenMint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([authority])
+      .rpc();
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    let user: Keypair;
+    ({ user, tokenAccount: destination } = await createFundedUser(mint, 1_000_000));
+    await pay(user, destination, mint, 500_000);
+
+    for (const [admin, limit] of [[limited, 50_000], [roomy, 200_000]] as const) {
+      await airdrop(admin.publicKey);
+      await program.methods.addAdmin(admin.publicKey, null, true, false).accounts({ authority: owner.publicKey }).rpc();
+      await program.methods
+        .setAdminBudget(admin.publicKey, mint, new BN(limit))
+        .accounts({ authority: owner.publicKey })
+        .rpc();
+    }
+  });
+
+  after(async () => {
+    for (const admin of [limited, roomy]) {
+      await program.methods.removeAdmin(admin.publicKey).accounts({ authority: owner.publicKey }).rpc();
+    }
+  });
+
+  it("stops an admin at their own budget while another keeps room", async () => {
+    await withdraw(limited, 50_000);
+    await expectError(withdraw(limited, 10_000), "AdminBudgetExceeded");
+
+    await withdraw(roomy, 100_000);
+    expect(await tokenBalance(destination)).to.equal(BigInt(650_000));
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(350_000));
+  });
+});
@@ -0,0 +1,37 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import { ensureInitialized, expectError, MAX_ADMINS, owner, program, programStatePda } from "./setup";
+
+describe("admin cap", () => {
+  const added: PublicKey[] = [];
+
+  const addAdmin = (admin: PublicKey) =>
+    program.methods.addAdmin(admin, null, true, true).accounts({ authority: owner.publicKey }).rpc();
+
+  before(async () => {
+    await ensureInitialized();
+  });
+
+  after(async () => {
+    for (const admin of added) {
+      await program.methods.removeAdmin(admin).accounts({ authority: owner.publicKey }).rpc();
+    }
+  });
+
+  it("rejects adding an admin past MAX_ADMINS", async () => {
+    const { admins } = await program.account.programState.fetch(programStatePda);
+    for (let i = admins.length; i < MAX_ADMINS; i++) {
+      const admin = Keypair.generate().publicKey;
+      await addAdmin(admin);
+      added.push(admin);
+    }
+
+    await expectError(addAdmin(Keypair.generate().publicKey), "TooManyAdmins");
+    expect((await program.account.programState.fetch(programStatePda)).admins).to.have.lengthOf(MAX_ADMINS);
+  });
+
+  it("returns every admin from get_admins at the cap", async () => {
+    const admins = await program.methods.getAdmins().view();
+    expect(admins).to.have.lengthOf(MAX_ADMINS);
+  });
+});
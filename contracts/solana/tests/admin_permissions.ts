@@ -0,0 +1,93 @@
+import { BN } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  airdrop,
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  owner,
+  pay,
+  program,
+  programStatePda,
+  programTokenAccount,
+} from "./setup";
+
+describe("admin permissions", () => {
+  const withdrawer = Keypair.generate();
+  const manager = Keypair.generate();
+  let mint: PublicKey;
+  let destinationTokenAccount: PublicKey;
+
+  const addAdmin = (admin: PublicKey, canWithdraw: boolean, canManage: boolean, authority: Keypair = owner) =>
+    program.methods
+      .addAdmin(admin, null, canWithdraw, canManage)
+      .accounts({ authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+  const withdraw = (authority: Keypair) =>
+    program.methods
+      .withdraw(new BN(1_000))
+      .accountsPartial({
+        authority: authority.publicKey,
+        programTokenAccount: programTokenAccount(mint),
+        destinationTokenAccount,
+        kycRegistry: null,
+        tokenMint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([authority])
+      .rpc();
+
+  before(async () => {
+    await ensureInitialized();
+    await airdrop(withdrawer.publicKey);
+    await airdrop(manager.publicKey);
+    await addAdmin(withdrawer.publicKey, true, false);
+    await addAdmin(manager.publicKey, false, true);
+
+    mint = await createAcceptedMint();
+    const { user, tokenAccount } = await createFundedUser(mint, 100_000);
+    await pay(user, tokenAccount, mint, 100_000);
+    destinationTokenAccount = tokenAccount;
+  });
+
+  after(async () => {
+    for (const admin of [withdrawer.publicKey, manager.publicKey]) {
+      await program.methods.removeAdmin(admin).accounts({ authority: owner.publicKey }).rpc();
+    }
+  });
+
+  it("stores the permissions of each admin", async () => {
+    const admins = await program.methods.getAdmins().view();
+    const entry = admins.find((admin) => admin.key.equals(withdrawer.publicKey));
+    expect(entry.canWithdraw).to.be.true;
+    expect(entry.canManage).to.be.false;
+  });
+
+  it("denies add_admin to a withdraw-only admin", async () => {
+    await expectError(addAdmin(Keypair.generate().publicKey, true, true, withdrawer), "UnauthorizedAccess");
+  });
+
+  it("denies a manage-only admin granting withdrawals", async () => {
+    await expectError(addAdmin(Keypair.generate().publicKey, true, false, manager), "PermissionNotHeld");
+    await expectError(addAdmin(Keypair.generate().publicKey, true, true, manager), "PermissionNotHeld");
+  });
+
+  it("denies withdrawals to a manage-only admin", async () => {
+    await expectError(withdraw(manager), "UnauthorizedAccess");
+  });
+
+  it("lets each admin use its own permission", async () => {
+    await withdraw(withdrawer);
+
+    const admin = Keypair.generate().publicKey;
+    await addAdmin(admin, false, false, manager);
+    const { admins } = await program.account.programState.fetch(programStatePda);
+    expect(admins.some((entry) => entry.key.equals(admin))).to.be.true;
+    await program.methods.removeAdmin(admin).accounts({ authority: owner.publicKey }).rpc();
+  });
+});
@@ -0,0 +1,57 @@
+import { TOKEN_PROGRAM_ID, getOrCreateAssociatedTokenAccount } from "@solana/spl-token";
+import { PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  owner,
+  pay,
+  program,
+  programTokenAccount,
+  provider,
+  tokenBalance,
+  transactionEvents,
+} from "./setup";
+
+describe("closing the program token account", () => {
+  let mint: PublicKey;
+  let destination: PublicKey;
+
+  const close = (sweepThenClose: boolean) =>
+    program.methods
+      .closeProgramTokenAccount(sweepThenClose)
+      .accountsPartial({
+        authority: owner.publicKey,
+        programTokenAccount: programTokenAccount(mint),
+        destinationTokenAccount: destination,
+        receiver: owner.publicKey,
+        tokenMint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    const { user, tokenAccount } = await createFundedUser(mint, 1_000_000);
+    await pay(user, tokenAccount, mint, 300_000);
+    destination = (await getOrCreateAssociatedTokenAccount(provider.connection, owner, mint, owner.publicKey)).address;
+  });
+
+  it("keeps a nonzero balance without the sweep", async () => {
+    await expectError(close(false), "TokenAccountNotEmpty");
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(300_000));
+  });
+
+  it("sweeps the balance then closes the account", async () => {
+    const signature = await close(true);
+
+    expect(await tokenBalance(destination)).to.equal(BigInt(300_000));
+    expect(await provider.connection.getAccountInfo(programTokenAccount(mint))).to.equal(null);
+    const [sweep] = await transactionEvents(signature, "sweepEvent");
+    expect(sweep.data.amount.toNumber()).to.equal(300_000);
+    expect(await transactionEvents(signature, "tokenAccountClosedEvent")).to.have.lengthOf(1);
+  });
+});
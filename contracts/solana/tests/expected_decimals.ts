@@ -0,0 +1,46 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  owner,
+  pay,
+  program,
+  programTokenAccount,
+  tokenBalance,
+} from "./setup";
+
+describe("expected decimals", () => {
+  let mint: PublicKey;
+  let user: Keypair;
+  let userTokenAccount: PublicKey;
+
+  const setExpectedDecimals = (expectedDecimals: number | null) =>
+    program.methods.setExpectedDecimals(mint, expectedDecimals).accounts({ authority: owner.publicKey }).rpc();
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    ({ user, tokenAccount: userTokenAccount } = await createFundedUser(mint, 1_000_000));
+  });
+
+  after(async () => {
+    await setExpectedDecimals(null);
+  });
+
+  it("rejects payments when the mint decimals do not match", async () => {
+    await setExpectedDecimals(9);
+
+    await expectError(pay(user, userTokenAccount, mint, 100_000), "InvalidTokenMint");
+    expect(await tokenBalance(userTokenAccount)).to.equal(BigInt(1_000_000));
+  });
+
+  it("accepts payments when the mint decimals match", async () => {
+    await setExpectedDecimals(6);
+
+    await pay(user, userTokenAccount, mint, 100_000);
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(100_000));
+  });
+});
@@ -0,0 +1,62 @@
+import { BN } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  owner,
+  pay,
+  program,
+  programTokenAccount,
+  transactionEvents,
+} from "./setup";
+
+describe("low balance events", () => {
+  let mint: PublicKey;
+  let destination: PublicKey;
+
+  const setLowBalanceThreshold = (threshold: number) =>
+    program.methods.setLowBalanceThreshold(mint, new BN(threshold)).accounts({ authority: owner.publicKey }).rpc();
+
+  const withdraw = (amount: number) =>
+    program.methods
+      .withdraw(new BN(amount))
+      .accountsPartial({
+        authority: owner.publicKey,
+        programTokenAccount: programTokenAccount(mint),
+        destinationTokenAccount: destination,
+        kycRegistry: null,
+        tokenMint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    let user: Keypair;
+    ({ user, tokenAccount: destination } = await createFundedUser(mint, 1_000_000));
+    await pay(user, destination, mint, 500_000);
+    await setLowBalanceThreshold(300_000);
+  });
+
+  after(async () => {
+    await setLowBalanceThreshold(0);
+  });
+
+  it("stays quiet while the balance is above the threshold", async () => {
+    const signature = await withdraw(100_000);
+    expect(await transactionEvents(signature, "lowBalanceEvent")).to.have.lengthOf(0);
+  });
+
+  it("fires once a withdrawal drops the balance below the threshold", async () => {
+    const signature = await withdraw(150_000);
+
+    const [event] = await transactionEvents(signature, "lowBalanceEvent");
+    expect(event.data.tokenMint.equals(mint)).to.equal(true);
+    expect(event.data.balance.toNumber()).to.equal(250_000);
+    expect(event.data.threshold.toNumber()).to.equal(300_000);
+  });
+});
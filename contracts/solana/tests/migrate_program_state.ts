@@ -0,0 +1,14 @@
+import { ensureInitialized, expectError, owner, program } from "./setup";
+
+describe("program state migration", () => {
+  before(async () => {
+    await ensureInitialized();
+  });
+
+  it("rejects a program state already in the current layout", async () => {
+    await expectError(
+      program.methods.migrateProgramState().accounts({ authority: owner.publicKey }).rpc(),
+      "ProgramStateAlreadyMigrated"
+    );
+  });
+});
@@ -0,0 +1,69 @@
+import { BN } from "@coral-xyz/anchor";
+import { createAccount, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  owner,
+  pay,
+  program,
+  programTokenAccount,
+  provider,
+  tokenBalance,
+} from "./setup";
+
+describe("treasury migration", () => {
+  let mint: PublicKey;
+  let approved: PublicKey;
+  let unapproved: PublicKey;
+
+  const setMigrationTarget = (migrationTarget: PublicKey | null) =>
+    program.methods.setMigrationTarget(mint, migrationTarget).accounts({ authority: owner.publicKey }).rpc();
+
+  const migrateTreasury = (destinationTokenAccount: PublicKey, amount: number) =>
+    program.methods
+      .migrateTreasury(new BN(amount))
+      .accountsPartial({
+        authority: owner.publicKey,
+        programTokenAccount: programTokenAccount(mint),
+        destinationTokenAccount,
+        tokenMint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    const { user, tokenAccount } = await createFundedUser(mint, 1_000_000);
+    await pay(user, tokenAccount, mint, 500_000);
+    approved = await createAccount(provider.connection, owner, mint, Keypair.generate().publicKey);
+    unapproved = await createAccount(provider.connection, owner, mint, Keypair.generate().publicKey);
+  });
+
+  after(async () => {
+    await setMigrationTarget(null);
+  });
+
+  it("rejects migrating before a target is set", async () => {
+    await expectError(migrateTreasury(approved, 100_000), "InvalidMigrationTarget");
+  });
+
+  it("rejects migrating to another account than the target", async () => {
+    await setMigrationTarget(approved);
+
+    await expectError(migrateTreasury(unapproved, 100_000), "InvalidMigrationTarget");
+    expect(await tokenBalance(unapproved)).to.equal(BigInt(0));
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(500_000));
+  });
+
+  it("migrates to the approved target", async () => {
+    await migrateTreasury(approved, 100_000);
+
+    expect(await tokenBalance(approved)).to.equal(BigInt(100_000));
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(400_000));
+  });
+});
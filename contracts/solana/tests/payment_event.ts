@@ -0,0 +1,31 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import { createAcceptedMint, createFundedUser, ensureInitialized, pay, transactionEvents } from "./setup";
+
+describe("payment events", () => {
+  let mint: PublicKey;
+  let user: Keypair;
+  let userTokenAccount: PublicKey;
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    ({ user, tokenAccount: userTokenAccount } = await createFundedUser(mint, 1_000_000));
+  });
+
+  it("carries a cumulative total equal to the sum of the amounts", async () => {
+    let sum = 0;
+    let previousTotal = 0;
+    for (const amount of [100_000, 1, 250_000]) {
+      const signature = await pay(user, userTokenAccount, mint, amount);
+      const [event] = await transactionEvents(signature, "paymentEvent");
+      sum += event.data.amount.toNumber();
+
+      const cumulativeTotal = event.data.cumulativeTotal.toNumber();
+      expect(cumulativeTotal).to.be.greaterThan(previousTotal);
+      expect(cumulativeTotal).to.equal(sum);
+      expect(event.data.creditedAmount.toNumber()).to.equal(amount);
+      previousTotal = cumulativeTotal;
+    }
+  });
+});
@@ -0,0 +1,50 @@
+import { BN } from "@coral-xyz/anchor";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  pay,
+  program,
+  receiptPda,
+  tokenBalance,
+} from "./setup";
+
+describe("payment receipts", () => {
+  let mint: PublicKey;
+  let user: Keypair;
+  let userTokenAccount: PublicKey;
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    ({ user, tokenAccount: userTokenAccount } = await createFundedUser(mint, 1_000_000));
+  });
+
+  it("stores the amount and timestamp of a referenced payment", async () => {
+    const referenceId = new BN(42);
+    await pay(user, userTokenAccount, mint, 100_000, referenceId);
+
+    const receipt = await program.account.paymentReceipt.fetch(receiptPda(user.publicKey, referenceId));
+    expect(receipt.user.equals(user.publicKey)).to.be.true;
+    expect(receipt.referenceId.toNumber()).to.equal(42);
+    expect(receipt.amount.toNumber()).to.equal(100_000);
+    expect(receipt.timestamp.toNumber()).to.be.greaterThan(0);
+  });
+
+  it("rejects a second payment with the same reference", async () => {
+    await expectError(pay(user, userTokenAccount, mint, 100_000, new BN(42)), "already in use");
+    expect(await tokenBalance(userTokenAccount)).to.equal(BigInt(900_000));
+  });
+
+  it("accepts the same reference from another user and payments without a reference", async () => {
+    const other = await createFundedUser(mint, 1_000_000);
+    await pay(other.user, other.tokenAccount, mint, 100_000, new BN(42));
+
+    await pay(user, userTokenAccount, mint, 100_000);
+    await pay(user, userTokenAccount, mint, 100_000);
+    expect(await tokenBalance(userTokenAccount)).to.equal(BigInt(700_000));
+  });
+});
@@ -0,0 +1,52 @@
+import { BN } from "@coral-xyz/anchor";
+import { TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  createTransferFeeMint,
+  ensureInitialized,
+  pay,
+  program,
+  programTokenAccount,
+  tokenBalance,
+} from "./setup";
+
+describe("net quotes", () => {
+  const quoteNet = (tokenMint: PublicKey, amount: number) =>
+    program.methods.quoteNet(new BN(amount)).accounts({ tokenMint }).view();
+
+  // Quotes `amount`, pays it and returns the quoted net with what the program token account received
+  const quoteAndPay = async (mint: PublicKey, amount: number, tokenProgram: PublicKey) => {
+    const { user, tokenAccount } = await createFundedUser(mint, 1_000_000, tokenProgram);
+    const quote = await quoteNet(mint, amount);
+    await pay(user, tokenAccount, mint, amount, null, { tokenProgram });
+    return { quote, received: await tokenBalance(programTokenAccount(mint), tokenProgram) };
+  };
+
+  before(async () => {
+    await ensureInitialized();
+  });
+
+  it("matches the amount received for a transfer fee mint", async () => {
+    // 2.5% fee capped at 2_000
+    const mint = await createTransferFeeMint(250, BigInt(2_000));
+
+    const { quote, received } = await quoteAndPay(mint, 50_000, TOKEN_2022_PROGRAM_ID);
+    expect(quote.gross.toNumber()).to.equal(50_000);
+    expect(quote.transferFeeEstimate.toNumber()).to.equal(1_250);
+    expect(BigInt(quote.net.toString())).to.equal(received);
+
+    const capped = await quoteNet(mint, 200_000);
+    expect(capped.transferFeeEstimate.toNumber()).to.equal(2_000);
+  });
+
+  it("quotes the full amount for an SPL Token mint", async () => {
+    const mint = await createAcceptedMint();
+
+    const { quote, received } = await quoteAndPay(mint, 50_000, TOKEN_PROGRAM_ID);
+    expect(quote.transferFeeEstimate.toNumber()).to.equal(0);
+    expect(BigInt(quote.net.toString())).to.equal(received);
+  });
+});
@@ -0,0 +1,112 @@
+import { BN } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  airdrop,
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  mintStatePda,
+  owner,
+  PAUSE_WITHDRAWALS,
+  pay,
+  program,
+  programTokenAccount,
+  tokenBalance,
+} from "./setup";
+
+describe("refunds", () => {
+  let mint: PublicKey;
+  let user: Keypair;
+  let userTokenAccount: PublicKey;
+
+  const refund = (amount: number, authority: Keypair = owner) =>
+    program.methods
+      .refund(new BN(amount))
+      .accountsPartial({
+        authority: authority.publicKey,
+        programTokenAccount: programTokenAccount(mint),
+        userTokenAccount,
+        kycRegistry: null,
+        tokenMint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([authority])
+      .rpc();
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    ({ user, tokenAccount: userTokenAccount } = await createFundedUser(mint, 1_000_000));
+    await pay(user, userTokenAccount, mint, 500_000);
+  });
+
+  it("returns tokens to the user token account", async () => {
+    await refund(100_000);
+
+    expect(await tokenBalance(userTokenAccount)).to.equal(BigInt(600_000));
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(400_000));
+    const mintState = await program.account.mintState.fetch(mintStatePda(mint));
+    expect(mintState.totalRefunded.toNumber()).to.equal(100_000);
+  });
+
+  it("rejects refunding more than the balance", async () => {
+    await expectError(refund(400_001), "InsufficientFunds");
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(400_000));
+  });
+
+  it("is paused along with withdrawals", async () => {
+    await program.methods.setPauseMask(PAUSE_WITHDRAWALS).accounts({ authority: owner.publicKey }).rpc();
+    try {
+      await expectError(refund(1_000), "ProgramPaused");
+    } finally {
+      await program.methods.setPauseMask(0).accounts({ authority: owner.publicKey }).rpc();
+    }
+  });
+
+  it("charges the admin budget", async () => {
+    const admin = Keypair.generate();
+    await airdrop(admin.publicKey);
+    await program.methods.addAdmin(admin.publicKey, null, true, false).accounts({ authority: owner.publicKey }).rpc();
+    await program.methods
+      .setAdminBudget(admin.publicKey, mint, new BN(50_000))
+      .accounts({ authority: owner.publicKey })
+      .rpc();
+
+    try {
+      await refund(40_000, admin);
+      await expectError(refund(20_000, admin), "AdminBudgetExceeded");
+      expect(await tokenBalance(userTokenAccount)).to.equal(BigInt(640_000));
+    } finally {
+      await program.methods.removeAdmin(admin.publicKey).accounts({ authority: owner.publicKey }).rpc();
+    }
+  });
+
+  it("ignores the minimum withdrawal", async () => {
+    const setMinWithdrawal = (minWithdrawal: number) =>
+      program.methods.setMinWithdrawal(mint, new BN(minWithdrawal)).accounts({ authority: owner.publicKey }).rpc();
+    const withdraw = (amount: number) =>
+      program.methods
+        .withdraw(new BN(amount))
+        .accountsPartial({
+          authority: owner.publicKey,
+          programTokenAccount: programTokenAccount(mint),
+          destinationTokenAccount: userTokenAccount,
+          kycRegistry: null,
+          tokenMint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    await setMinWithdrawal(50_000);
+    try {
+      await refund(1_000);
+      await expectError(withdraw(1_000), "WithdrawalTooSmall");
+      expect(await tokenBalance(userTokenAccount)).to.equal(BigInt(641_000));
+    } finally {
+      await setMinWithdrawal(0);
+    }
+  });
+});
@@ -0,0 +1,54 @@
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  mintStatePda,
+  owner,
+  pay,
+  program,
+  programTokenAccount,
+  provider,
+  tokenBalance,
+} from "./setup";
+
+describe("reseeding the program token account", () => {
+  let mint: PublicKey;
+  let user: Keypair;
+  let userTokenAccount: PublicKey;
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    ({ user, tokenAccount: userTokenAccount } = await createFundedUser(mint, 1_000_000));
+    await pay(user, userTokenAccount, mint, 200_000);
+  });
+
+  it("moves the balance to the new seed version", async () => {
+    await program.methods
+      .reseedProgramTokenAccount(1)
+      .accountsPartial({
+        authority: owner.publicKey,
+        oldProgramTokenAccount: programTokenAccount(mint),
+        newProgramTokenAccount: programTokenAccount(mint, 1),
+        tokenMint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    expect(await tokenBalance(programTokenAccount(mint, 1))).to.equal(BigInt(200_000));
+    expect(await provider.connection.getAccountInfo(programTokenAccount(mint))).to.equal(null);
+    const mintState = await program.account.mintState.fetch(mintStatePda(mint));
+    expect(mintState.programTokenSeedVersion).to.equal(1);
+  });
+
+  it("receives later payments in the new program token account", async () => {
+    await pay(user, userTokenAccount, mint, 100_000, null, { programTokenAccount: programTokenAccount(mint, 1) });
+    expect(await tokenBalance(programTokenAccount(mint, 1))).to.equal(BigInt(300_000));
+
+    await expectError(pay(user, userTokenAccount, mint, 100_000), "ConstraintSeeds");
+  });
+});
@@ -0,0 +1,161 @@
+import * as anchor from "@coral-xyz/anchor";
+import { BN, Program } from "@coral-xyz/anchor";
+import {
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  ExtensionType,
+  getAccount,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+} from "@solana/spl-token";
+import { Keypair, LAMPORTS_PER_SOL, PublicKey, sendAndConfirmTransaction, SystemProgram, Transaction } from "@solana/web3.js";
+import { expect } from "chai";
+import { LibertaiPaymentProcessor } from "../target/types/libertai_payment_processor";
+import { getProgramTokenAccount } from "../cli/utils";
+
+export const provider = anchor.AnchorProvider.env();
+anchor.setProvider(provider);
+
+export const program = anchor.workspace.LibertaiPaymentProcessor as Program<LibertaiPaymentProcessor>;
+export const owner = (provider.wallet as anchor.Wallet).payer;
+
+export const MAX_ADMINS = 23;
+export const PAUSE_WITHDRAWALS = 1 << 1;
+
+const BPF_LOADER_UPGRADEABLE_PROGRAM_ID = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+
+export const programStatePda = PublicKey.findProgramAddressSync([Buffer.from("program_state")], program.programId)[0];
+
+export const mintStatePda = (mint: PublicKey): PublicKey =>
+  PublicKey.findProgramAddressSync([Buffer.from("mint_state"), mint.toBuffer()], program.programId)[0];
+
+export const receiptPda = (user: PublicKey, referenceId: BN): PublicKey =>
+  PublicKey.findProgramAddressSync(
+    [Buffer.from("receipt"), user.toBuffer(), referenceId.toArrayLike(Buffer, "le", 8)],
+    program.programId
+  )[0];
+
+export const programTokenAccount = (mint: PublicKey, seedVersion = 0): PublicKey =>
+  getProgramTokenAccount(program.programId, mint, seedVersion);
+
+// Every test file shares the validator's program state, whichever runs first initializes it
+export const ensureInitialized = async () => {
+  if (await provider.connection.getAccountInfo(programStatePda)) {
+    return;
+  }
+
+  const [programData] = PublicKey.findProgramAddressSync([program.programId.toBuffer()], BPF_LOADER_UPGRADEABLE_PROGRAM_ID);
+  await program.methods.initialize(owner.publicKey).accounts({ payer: owner.publicKey, programData }).rpc();
+};
+
+export const airdrop = async (to: PublicKey, sol = 2) => {
+  const signature = await provider.connection.requestAirdrop(to, sol * LAMPORTS_PER_SOL);
+  await provider.connection.confirmTransaction(signature, "confirmed");
+};
+
+// Fresh SPL Token mint, minted by the owner and added to the accepted mints
+export const createAcceptedMint = async (): Promise<PublicKey> => {
+  const mint = await createMint(provider.connection, owner, owner.publicKey, null, 6);
+  await program.methods.addAcceptedMint().accounts({ authority: owner.publicKey, tokenMint: mint }).rpc();
+  return mint;
+};
+
+// Fresh Token-2022 mint withholding `feeBps` of every transfer, up to `maxFee`, added to the accepted mints
+export const createTransferFeeMint = async (feeBps: number, maxFee: bigint): Promise<PublicKey> => {
+  const mint = Keypair.generate();
+  const space = getMintLen([ExtensionType.TransferFeeConfig]);
+  const lamports = await provider.connection.getMinimumBalanceForRentExemption(space);
+  const transaction = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: owner.publicKey,
+      newAccountPubkey: mint.publicKey,
+      space,
+      lamports,
+      programId: TOKEN_2022_PROGRAM_ID,
+    }),
+    createInitializeTransferFeeConfigInstruction(
+      mint.publicKey,
+      owner.publicKey,
+      owner.publicKey,
+      feeBps,
+      maxFee,
+      TOKEN_2022_PROGRAM_ID,
+    ),
+    createInitializeMintInstruction(mint.publicKey, 6, owner.publicKey, null, TOKEN_2022_PROGRAM_ID),
+  );
+  await sendAndConfirmTransaction(provider.connection, transaction, [owner, mint]);
+  await program.methods.addAcceptedMint().accounts({ authority: owner.publicKey, tokenMint: mint.publicKey }).rpc();
+  return mint.publicKey;
+};
+
+export const createFundedUser = async (mint: PublicKey, amount: number, tokenProgram = TOKEN_PROGRAM_ID) => {
+  const user = Keypair.generate();
+  await airdrop(user.publicKey);
+  const tokenAccount = await getOrCreateAssociatedTokenAccount(
+    provider.connection,
+    owner,
+    mint,
+    user.publicKey,
+    false,
+    undefined,
+    undefined,
+    tokenProgram,
+  );
+  await mintTo(provider.connection, owner, mint, tokenAccount.address, owner, amount, [], undefined, tokenProgram);
+  return { user, tokenAccount: tokenAccount.address };
+};
+
+export const tokenBalance = async (tokenAccount: PublicKey, tokenProgram = TOKEN_PROGRAM_ID): Promise<bigint> =>
+  (await getAccount(provider.connection, tokenAccount, undefined, tokenProgram)).amount;
+
+// `accounts` overrides the defaults, e.g. the program token account of another seed version
+export const pay = (
+  user: Keypair,
+  userTokenAccount: PublicKey,
+  mint: PublicKey,
+  amount: number,
+  referenceId: BN | null = null,
+  accounts: { programTokenAccount?: PublicKey; coldTreasury?: PublicKey | null; tokenProgram?: PublicKey } = {},
+) =>
+  program.methods
+    .processPayment(new BN(amount), referenceId, "")
+    .accountsPartial({
+      user: user.publicKey,
+      userTokenAccount,
+      programTokenAccount: programTokenAccount(mint),
+      tokenMint: mint,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      coldTreasury: null,
+      paymentReceipt: referenceId ? receiptPda(user.publicKey, referenceId) : null,
+      ...accounts,
+    })
+    .signers([user])
+    .rpc();
+
+// Events emitted by the program in a confirmed transaction, by name
+export const transactionEvents = async (signature: string, name: string) => {
+  await provider.connection.confirmTransaction(signature, "confirmed");
+  const transaction = await provider.connection.getTransaction(signature, {
+    commitment: "confirmed",
+    maxSupportedTransactionVersion: 0,
+  });
+  const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+  return Array.from(parser.parseLogs(transaction.meta.logMessages)).filter((event) => event.name === name);
+};
+
+// Program errors are matched on their name, runtime errors such as `already in use` on the logs
+export const expectError = async (promise: Promise<unknown>, expected: string) => {
+  try {
+    await promise;
+  } catch (err) {
+    const logs: string[] = err.logs ?? err.transactionLogs ?? [];
+    const matched = err.error?.errorCode?.code === expected || logs.some((log) => log.includes(expected));
+    expect(matched, `expected ${expected}, got ${err}`).to.be.true;
+    return;
+  }
+  expect.fail(`expected ${expected}, but the transaction succeeded`);
+};
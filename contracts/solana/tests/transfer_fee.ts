@@ -0,0 +1,38 @@
+import { TOKEN_2022_PROGRAM_ID } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTransferFeeMint,
+  ensureInitialized,
+  mintStatePda,
+  pay,
+  program,
+  programTokenAccount,
+  tokenBalance,
+  transactionEvents,
+} from "./setup";
+
+describe("transfer fee mints", () => {
+  let mint: PublicKey;
+  let user: Keypair;
+  let userTokenAccount: PublicKey;
+
+  before(async () => {
+    await ensureInitialized();
+    // 1% fee, capped well above the amounts paid here
+    mint = await createTransferFeeMint(100, BigInt(1_000_000));
+    ({ user, tokenAccount: userTokenAccount } = await createFundedUser(mint, 1_000_000, TOKEN_2022_PROGRAM_ID));
+  });
+
+  it("reports the net amount received in the payment event", async () => {
+    const signature = await pay(user, userTokenAccount, mint, 100_000, null, { tokenProgram: TOKEN_2022_PROGRAM_ID });
+
+    const [event] = await transactionEvents(signature, "paymentEvent");
+    expect(event.data.amount.toNumber()).to.equal(99_000);
+    expect(event.data.creditedAmount.toNumber()).to.equal(99_000);
+    expect(await tokenBalance(programTokenAccount(mint), TOKEN_2022_PROGRAM_ID)).to.equal(BigInt(99_000));
+    const mintState = await program.account.mintState.fetch(mintStatePda(mint));
+    expect(mintState.totalProcessed.toNumber()).to.equal(99_000);
+  });
+});
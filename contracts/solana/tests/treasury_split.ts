@@ -0,0 +1,57 @@
+import { BN } from "@coral-xyz/anchor";
+import { getOrCreateAssociatedTokenAccount } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  owner,
+  pay,
+  program,
+  programTokenAccount,
+  provider,
+  tokenBalance,
+  transactionEvents,
+} from "./setup";
+
+describe("hot and cold treasury split", () => {
+  let mint: PublicKey;
+  let user: Keypair;
+  let userTokenAccount: PublicKey;
+  let coldTreasury: PublicKey;
+
+  const setTreasurySplit = (hotBalanceTarget: number, cold: PublicKey | null) =>
+    program.methods
+      .setTreasurySplit(mint, new BN(hotBalanceTarget), cold)
+      .accounts({ authority: owner.publicKey })
+      .rpc();
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    ({ user, tokenAccount: userTokenAccount } = await createFundedUser(mint, 1_000_000));
+    coldTreasury = (await getOrCreateAssociatedTokenAccount(provider.connection, owner, mint, owner.publicKey)).address;
+    await setTreasurySplit(150_000, coldTreasury);
+  });
+
+  after(async () => {
+    await setTreasurySplit(0, null);
+  });
+
+  it("keeps payments below the target in the hot account", async () => {
+    await pay(user, userTokenAccount, mint, 100_000, null, { coldTreasury });
+
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(100_000));
+    expect(await tokenBalance(coldTreasury)).to.equal(BigInt(0));
+  });
+
+  it("forwards the overflow above the target to the cold treasury", async () => {
+    const signature = await pay(user, userTokenAccount, mint, 200_000, null, { coldTreasury });
+
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(150_000));
+    expect(await tokenBalance(coldTreasury)).to.equal(BigInt(150_000));
+    const [coldTransfer] = await transactionEvents(signature, "coldTransferEvent");
+    expect(coldTransfer.data.amount.toNumber()).to.equal(150_000);
+  });
+});
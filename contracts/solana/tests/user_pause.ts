@@ -0,0 +1,44 @@
+import { BN } from "@coral-xyz/anchor";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  owner,
+  pay,
+  program,
+  programTokenAccount,
+  provider,
+  tokenBalance,
+} from "./setup";
+
+// Unix timestamp of the latest confirmed block, the clock payments are checked against
+const chainTime = async () => provider.connection.getBlockTime(await provider.connection.getSlot("confirmed"));
+
+describe("user pauses", () => {
+  let mint: PublicKey;
+  let user: Keypair;
+  let userTokenAccount: PublicKey;
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    ({ user, tokenAccount: userTokenAccount } = await createFundedUser(mint, 1_000_000));
+  });
+
+  it("rejects payments until the pause expires", async () => {
+    const untilTs = (await chainTime()) + 3;
+    await program.methods.pauseUser(user.publicKey, new BN(untilTs)).accounts({ authority: owner.publicKey }).rpc();
+
+    await expectError(pay(user, userTokenAccount, mint, 100_000), "UserPaused");
+    expect(await tokenBalance(userTokenAccount)).to.equal(BigInt(1_000_000));
+
+    while ((await chainTime()) <= untilTs) {
+      await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+    await pay(user, userTokenAccount, mint, 100_000);
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(100_000));
+  });
+});
@@ -0,0 +1,71 @@
+import { BN } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createAcceptedMint,
+  createFundedUser,
+  ensureInitialized,
+  expectError,
+  owner,
+  pay,
+  program,
+  programTokenAccount,
+  tokenBalance,
+} from "./setup";
+
+describe("idempotent withdrawals", () => {
+  let mint: PublicKey;
+  let destination: PublicKey;
+  const nonce = new BN(1);
+
+  const withdrawNoncePda = (authority: PublicKey, tokenMint: PublicKey, withdrawNonce: BN) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("withdraw_nonce"), authority.toBuffer(), tokenMint.toBuffer(), withdrawNonce.toArrayLike(Buffer, "le", 8)],
+      program.programId,
+    )[0];
+
+  const withdrawIdempotent = (amount: number, failOnReuse = false) =>
+    program.methods
+      .withdrawIdempotent(new BN(amount), nonce, failOnReuse)
+      .accountsPartial({
+        authority: owner.publicKey,
+        withdrawNonce: withdrawNoncePda(owner.publicKey, mint, nonce),
+        programTokenAccount: programTokenAccount(mint),
+        destinationTokenAccount: destination,
+        kycRegistry: null,
+        tokenMint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+  const isNonceUsed = (tokenMint: PublicKey) =>
+    program.methods
+      .isNonceUsed({ withdraw: { authority: owner.publicKey, tokenMint } }, nonce)
+      .accounts({ nonceAccount: withdrawNoncePda(owner.publicKey, tokenMint, nonce) })
+      .view();
+
+  before(async () => {
+    await ensureInitialized();
+    mint = await createAcceptedMint();
+    let user: Keypair;
+    ({ user, tokenAccount: destination } = await createFundedUser(mint, 1_000_000));
+    await pay(user, destination, mint, 500_000);
+  });
+
+  it("transfers once for a repeated nonce", async () => {
+    await withdrawIdempotent(100_000);
+    await withdrawIdempotent(100_000);
+
+    expect(await tokenBalance(destination)).to.equal(BigInt(600_000));
+    expect(await tokenBalance(programTokenAccount(mint))).to.equal(BigInt(400_000));
+    await expectError(withdrawIdempotent(100_000, true), "NonceReused");
+  });
+
+  it("scopes nonces to the mint", async () => {
+    const otherMint = await createAcceptedMint();
+
+    expect(await isNonceUsed(mint)).to.equal(true);
+    expect(await isNonceUsed(otherMint)).to.equal(false);
+  });
+});
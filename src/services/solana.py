@@ -51,6 +51,46 @@ class SolanaService:
         """Calculates the 8-byte Anchor event discriminator."""
         return hashlib.sha256(f"event:{event_name}".encode()).digest()[:8]
 
+    @staticmethod
+    def _read_credited_amount(event_data: bytes, offset: int) -> int | None:
+        """Reads PaymentEvent.credited_amount from the timestamp offset, None for events emitted before it."""
+        # timestamp, token_mint and cumulative_total
+        offset += 8 + 32 + 8
+        # treasury_balance is an Option<u64>
+        if len(event_data) < offset + 1:
+            return None
+        offset += 9 if event_data[offset] == 1 else 1
+        # memo is a length-prefixed string
+        if len(event_data) < offset + 4:
+            return None
+        offset += 4 + struct.unpack("<I", event_data[offset : offset + 4])[0]
+        # account_creation_fee
+        offset += 8
+        if len(event_data) < offset + 8:
+            return None
+        return struct.unpack("<Q", event_data[offset : offset + 8])[0]
+
+    def get_public_stats_address(self, token_mint: Pubkey) -> Pubkey:
+        """Address of the PublicStats PDA of a token mint, payment totals being kept per mint."""
+        return Pubkey.find_program_address([b"public_stats", bytes(token_mint)], self.program_id)[0]
+
+    @staticmethod
+    def _decode_public_stats(data: bytes) -> dict:
+        """Decodes the stable PublicStats layout, after its 8-byte discriminator."""
+        total_processed, payment_count, last_update_slot = struct.unpack("<QQQ", data[8:32])
+        return {
+            "total_processed": total_processed,
+            "payment_count": payment_count,
+            "last_update_slot": last_update_slot,
+        }
+
+    async def get_public_stats(self, token_mint: Pubkey) -> dict | None:
+        """Get the payment totals of a token mint, None before its first payment"""
+        account = await asyncio.to_thread(self.client.get_account_info, self.get_public_stats_address(token_mint))
+        if account.value is None:
+            return None
+        return self._decode_public_stats(bytes(account.value.data))
+
     def extract_payment_event(self, meta):
         """Extract PaymentEvent or SolPaymentEvent data from transaction metadata"""
 
@@ -74,9 +114,16 @@ class SolanaService:
                             user = str(Pubkey(event_data[offset : offset + 32]))
                             offset += 32
                             amount = struct.unpack("<Q", event_data[offset : offset + 8])[0]
-                            # skip timestamp and token_mint
-
-                            return {"user": user, "amount": amount, "status": status, "event_type": "token_payment"}
+                            offset += 8
+                            # Discounted payments are credited above the tokens received
+                            credited_amount = self._read_credited_amount(event_data, offset)
+
+                            return {
+                                "user": user,
+                                "amount": amount if credited_amount is None else credited_amount,
+                                "status": status,
+                                "event_type": "token_payment",
+                            }
 
                     # Check for SolPaymentEvent discriminator
                     # Ensure data is long enough
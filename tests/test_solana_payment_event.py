@@ -0,0 +1,45 @@
+"""Tests for decoding the Solana program's PaymentEvent from transaction logs."""
+
+import base64
+import struct
+
+from solders.pubkey import Pubkey
+
+from src.interfaces.credits import CreditTransactionStatus
+from src.services.solana import SolanaService
+
+USER = Pubkey.from_string("11111111111111111111111111111112")
+MINT = Pubkey.from_string("mntpN8z1d29f3MWhMD7VqZFpeYmbD88MgwS3Bkz8y7u")
+
+
+def _payment_event(amount: int, credited_amount: int | None, memo: bytes = b"", treasury_balance: int | None = None):
+    data = SolanaService._get_event_discriminator("PaymentEvent")
+    data += bytes(USER) + struct.pack("<Qq", amount, 1_700_000_000) + bytes(MINT) + struct.pack("<Q", amount)
+    if credited_amount is not None:
+        data += b"\x00" if treasury_balance is None else b"\x01" + struct.pack("<Q", treasury_balance)
+        data += struct.pack("<I", len(memo)) + memo
+        data += struct.pack("<QQ", 0, credited_amount)
+    return {"err": None, "logMessages": [f"Program data: {base64.b64encode(data).decode()}"]}
+
+
+def _extract(meta: dict) -> dict | None:
+    # Decoding needs no RPC client
+    return SolanaService.__new__(SolanaService).extract_payment_event(meta)
+
+
+def test_payment_event_is_credited_with_the_credited_amount():
+    event = _extract(_payment_event(amount=80, credited_amount=100, memo=b"invoice-42", treasury_balance=500))
+    assert event == {
+        "user": str(USER),
+        "amount": 100,
+        "status": CreditTransactionStatus.completed,
+        "event_type": "token_payment",
+    }
+
+
+def test_payment_event_without_a_treasury_balance():
+    assert _extract(_payment_event(amount=80, credited_amount=100))["amount"] == 100
+
+
+def test_payment_event_from_before_credited_amount_uses_the_amount():
+    assert _extract(_payment_event(amount=80, credited_amount=None))["amount"] == 80
@@ -0,0 +1,34 @@
+"""Tests for locating and decoding the Solana program's per-mint PublicStats account."""
+
+import struct
+
+from solders.pubkey import Pubkey
+
+from src.services.solana import SolanaService
+
+PROGRAM_ID = Pubkey.from_string("21tHgbgC8Q6hoXyHx5pp3Gtp97B3HxrS2yH832ryeNSw")
+MINT = Pubkey.from_string("mntpN8z1d29f3MWhMD7VqZFpeYmbD88MgwS3Bkz8y7u")
+OTHER_MINT = Pubkey.from_string("So11111111111111111111111111111111111111112")
+
+
+def _service() -> SolanaService:
+    # Address derivation needs no RPC client
+    service = SolanaService.__new__(SolanaService)
+    service.program_id = PROGRAM_ID
+    return service
+
+
+def test_public_stats_address_is_derived_per_mint():
+    service = _service()
+    expected = Pubkey.find_program_address([b"public_stats", bytes(MINT)], PROGRAM_ID)[0]
+    assert service.get_public_stats_address(MINT) == expected
+    assert service.get_public_stats_address(OTHER_MINT) != expected
+
+
+def test_public_stats_layout_is_decoded():
+    data = b"\x00" * 8 + struct.pack("<QQQ", 1_500, 3, 42)
+    assert SolanaService._decode_public_stats(data) == {
+        "total_processed": 1_500,
+        "payment_count": 3,
+        "last_update_slot": 42,
+    }